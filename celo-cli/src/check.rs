@@ -0,0 +1,165 @@
+use std::fmt::Write;
+
+use celo::compiler::{
+    diagnostic::{Diagnostic, Span},
+    source::Source,
+    Compiler,
+};
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    Human,
+    /// One JSON object per diagnostic and line
+    Json,
+    /// A SARIF 2.1.0 log for code scanning tools
+    Sarif,
+}
+
+/// Compiles a file without running it and reports its diagnostics.
+pub fn check(path: &str, format: MessageFormat) -> i32 {
+    let result = Source::load(path).and_then(|source| Compiler::new(source).check());
+    let diagnostics: Vec<Diagnostic> = result.err().iter().map(Diagnostic::from).collect();
+    let output = render(&diagnostics, format);
+    match format {
+        MessageFormat::Human => eprint!("{output}"),
+        MessageFormat::Json | MessageFormat::Sarif => print!("{output}"),
+    }
+    if diagnostics.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Renders diagnostics in a message format, each line ending with a line break.
+pub fn render(diagnostics: &[Diagnostic], format: MessageFormat) -> String {
+    let mut output = String::new();
+    match format {
+        MessageFormat::Human => {
+            for diagnostic in diagnostics {
+                let severity = diagnostic.severity.name();
+                let position = position(diagnostic);
+                _ = writeln!(output, "{severity}[{}]: {position}", diagnostic.code);
+                for related in &diagnostic.related {
+                    let span = &related.span;
+                    let (line, column) = (span.start.line, span.start.column);
+                    let message = &related.message;
+                    _ = writeln!(output, "note: {}:{line}:{column}: {message}", span.path);
+                }
+            }
+        }
+        MessageFormat::Json => {
+            for diagnostic in diagnostics {
+                _ = writeln!(output, "{}", json_diagnostic(diagnostic));
+            }
+        }
+        MessageFormat::Sarif => _ = writeln!(output, "{}", sarif(diagnostics)),
+    }
+    output
+}
+
+fn position(diagnostic: &Diagnostic) -> String {
+    match &diagnostic.span {
+        Some(span) => format!(
+            "{}:{}:{}: {}",
+            diagnostic.path, span.start.line, span.start.column, diagnostic.message
+        ),
+        None => format!("{}: {}", diagnostic.path, diagnostic.message),
+    }
+}
+
+fn json_diagnostic(diagnostic: &Diagnostic) -> Value {
+    let related: Vec<Value> = diagnostic
+        .related
+        .iter()
+        .map(|related| {
+            let mut value = json_span(Some(&related.span));
+            value["message"] = related.message.as_str().into();
+            value["file"] = (*related.span.path).into();
+            value
+        })
+        .collect();
+    let mut value = json_span(diagnostic.span.as_ref());
+    value["severity"] = diagnostic.severity.name().into();
+    value["code"] = diagnostic.code.into();
+    value["name"] = diagnostic.name.into();
+    value["message"] = diagnostic.message.as_str().into();
+    value["file"] = (*diagnostic.path).into();
+    value["related"] = related.into();
+    value
+}
+
+fn json_span(span: Option<&Span>) -> Value {
+    let Some(span) = span else {
+        return json!({ "range": null, "start": null, "end": null });
+    };
+    json!({
+        "range": { "start": span.location.start, "end": span.location.end },
+        "start": { "line": span.start.line, "column": span.start.column },
+        "end": { "line": span.end.line, "column": span.end.column },
+    })
+}
+
+fn sarif(diagnostics: &[Diagnostic]) -> Value {
+    let mut rules: Vec<(&str, &str)> = diagnostics.iter().map(|d| (d.code, d.name)).collect();
+    rules.sort_unstable();
+    rules.dedup();
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let related: Vec<Value> = diagnostic
+                .related
+                .iter()
+                .enumerate()
+                .map(|(id, related)| {
+                    let mut location = sarif_location(&related.span.path, Some(&related.span));
+                    location["id"] = id.into();
+                    location["message"] = json!({ "text": related.message });
+                    location
+                })
+                .collect();
+            json!({
+                "ruleId": diagnostic.code,
+                "level": diagnostic.severity.name(),
+                "message": { "text": diagnostic.message },
+                "locations": [sarif_location(&diagnostic.path, diagnostic.span.as_ref())],
+                "relatedLocations": related,
+            })
+        })
+        .collect();
+    let rules: Vec<Value> = rules
+        .iter()
+        .map(|(id, name)| json!({ "id": id, "name": name }))
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "celo",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+fn sarif_location(path: &str, span: Option<&Span>) -> Value {
+    let mut location = json!({ "physicalLocation": { "artifactLocation": { "uri": path } } });
+    if let Some(span) = span {
+        location["physicalLocation"]["region"] = json!({
+            "startLine": span.start.line,
+            "startColumn": span.start.column,
+            "endLine": span.end.line,
+            "endColumn": span.end.column,
+            "byteOffset": span.location.start,
+            "byteLength": span.location.end - span.location.start,
+        });
+    }
+    location
+}
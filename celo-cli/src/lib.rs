@@ -1,8 +1,255 @@
-use celo::compiler::{source::Source, Compiler};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+
+use celo::{
+    compiler::{effect, error, lowering::LowerMirStep, source::Source, Compiler},
+    doc::{self, DocFormat},
+    format,
+};
+use maquina::vm::{bytecode, program::Program, Vm};
+
+use self::check::MessageFormat;
+
+pub mod check;
+mod repl;
+mod test;
+pub mod tokens;
+
+const USAGE: &str = "usage: celo <file>
+       celo build <file> [-o <output>]
+       celo run <file>
+       celo check [--message-format=human|json|sarif] <file>
+       celo fmt [--check] <file>...
+       celo doc [--format markdown|html] [-o <directory>] <file>
+       celo explain <code>
+       celo repl
+       celo test <file> [<filter>]
+       celo tokens [--format text|json] <file>";
 
 pub fn main(args: &[String]) -> i32 {
-    let main_source = Source::load(args[0].as_str()).expect("load source");
-    let mut compiler = Compiler::new(main_source);
-    compiler.compile().expect("compile program");
+    match args.first().map(String::as_str) {
+        Some("build") => build(&args[1..]),
+        Some("run") => run(&args[1..]),
+        Some("check") => check(&args[1..]),
+        Some("fmt") => fmt(&args[1..]),
+        Some("doc") => doc(&args[1..]),
+        Some("explain") => explain(&args[1..]),
+        Some("repl") if args.len() == 1 => repl::repl(),
+        Some("test") => match &args[1..] {
+            [path] => test::test(path, None),
+            [path, filter] => test::test(path, Some(filter)),
+            _ => {
+                eprintln!("{USAGE}");
+                1
+            }
+        },
+        Some("tokens") => match &args[1..] {
+            [path] => tokens::tokens(path, false),
+            [flag, format, path] if flag == "--format" && format == "text" => {
+                tokens::tokens(path, false)
+            }
+            [flag, format, path] if flag == "--format" && format == "json" => {
+                tokens::tokens(path, true)
+            }
+            _ => {
+                eprintln!("{USAGE}");
+                1
+            }
+        },
+        Some(_) if args.len() == 1 => run(args),
+        _ => {
+            eprintln!("{USAGE}");
+            1
+        }
+    }
+}
+
+fn build(args: &[String]) -> i32 {
+    let (path, output) = match args {
+        [path] => (path, Path::new(path).with_extension("maq")),
+        [path, flag, output] if flag == "-o" => (path, output.into()),
+        _ => {
+            eprintln!("{USAGE}");
+            return 1;
+        }
+    };
+    let Some(program) = compile(path) else {
+        return 1;
+    };
+    let result = File::create(&output)
+        .and_then(|file| bytecode::write_program(&mut BufWriter::new(file), &program));
+    if let Err(err) = result {
+        eprintln!("error: could not write {}: {err}", output.display());
+        return 1;
+    }
     0
 }
+
+fn run(args: &[String]) -> i32 {
+    let [path] = args else {
+        eprintln!("{USAGE}");
+        return 1;
+    };
+    let Some(program) = compile(path) else {
+        return 1;
+    };
+    if let Err(err) = Vm::new(program).run() {
+        eprintln!("{err}");
+        return 1;
+    }
+    0
+}
+
+fn check(args: &[String]) -> i32 {
+    let (format, path) = match args {
+        [path] => (Some("human"), path),
+        [flag, path] => (flag.strip_prefix("--message-format="), path),
+        _ => {
+            eprintln!("{USAGE}");
+            return 1;
+        }
+    };
+    let format = match format {
+        Some("human") => MessageFormat::Human,
+        Some("json") => MessageFormat::Json,
+        Some("sarif") => MessageFormat::Sarif,
+        _ => {
+            eprintln!("{USAGE}");
+            return 1;
+        }
+    };
+    check::check(path, format)
+}
+
+/// Formats files in place, or with `--check` only reports the files that are not formatted.
+fn fmt(args: &[String]) -> i32 {
+    let check = args.first().is_some_and(|arg| arg == "--check");
+    let paths = &args[check as usize..];
+    if paths.is_empty() {
+        eprintln!("{USAGE}");
+        return 1;
+    }
+    let mut status = 0;
+    for path in paths {
+        let source = match Source::load(path.as_str()) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("error: {err}");
+                status = 1;
+                continue;
+            }
+        };
+        let formatted = match format::format(&source) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                eprintln!("error: {err}");
+                status = 1;
+                continue;
+            }
+        };
+        if *formatted == *source.content {
+            continue;
+        }
+        if check {
+            eprintln!("{path} is not formatted");
+            status = 1;
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("error: could not write {path}: {err}");
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Writes documentation pages for a file and its imports, `doc` by default.
+fn doc(args: &[String]) -> i32 {
+    let mut format = DocFormat::Html;
+    let mut output = Path::new("doc").to_path_buf();
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("markdown") => format = DocFormat::Markdown,
+                Some("html") => format = DocFormat::Html,
+                _ => {
+                    eprintln!("{USAGE}");
+                    return 1;
+                }
+            },
+            "-o" => {
+                let Some(directory) = args.next() else {
+                    eprintln!("{USAGE}");
+                    return 1;
+                };
+                output = directory.into();
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return 1;
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{USAGE}");
+        return 1;
+    };
+    let result = Source::load(path.as_str()).and_then(|source| {
+        let hir = Compiler::new(source).parse()?;
+        let (mir, _) = LowerMirStep::new(&hir).run_incremental()?;
+        Ok((hir, effect::infer(&mir)))
+    });
+    let (hir, effects) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return 1;
+        }
+    };
+    let root = Path::new(path).parent().unwrap_or(Path::new(""));
+    let pages = doc::generate(&hir, &effects, root, format);
+    let result = fs::create_dir_all(&output).and_then(|()| {
+        pages
+            .iter()
+            .try_for_each(|page| fs::write(output.join(&page.file_name), &page.content))
+    });
+    if let Err(err) = result {
+        eprintln!("error: could not write {}: {err}", output.display());
+        return 1;
+    }
+    0
+}
+
+/// Prints the long-form explanation of an error code.
+fn explain(args: &[String]) -> i32 {
+    let [code] = args else {
+        eprintln!("{USAGE}");
+        return 1;
+    };
+    let Some(code) = error::find_error_code(code) else {
+        eprintln!("error: `{code}` is not an error code");
+        return 1;
+    };
+    println!(
+        "{} {}\n\n{}",
+        code.code,
+        code.name,
+        code.explanation.trim_end()
+    );
+    0
+}
+
+fn compile(path: &str) -> Option<Program> {
+    let result = Source::load(path).and_then(|source| Compiler::new(source).compile());
+    match result {
+        Ok(program) => Some(program),
+        Err(err) => {
+            eprintln!("error: {err}");
+            None
+        }
+    }
+}
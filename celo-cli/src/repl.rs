@@ -0,0 +1,63 @@
+use std::io::{self, BufRead, Write};
+
+use celo::compiler::{
+    session::{self, Session},
+    source::Source,
+};
+use maquina::vm::{program::Program, Vm};
+
+/// Reads entries from stdin and runs them on a persistent VM, printing the stack after each.
+pub fn repl() -> i32 {
+    let mut session = Session::default();
+    let mut vm = Vm::new(Program::default());
+    let mut stdin = io::stdin().lock();
+    let mut buffer = String::new();
+    let mut entry = 0;
+    loop {
+        print!("{}", if buffer.is_empty() { "> " } else { ". " });
+        _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => buffer.push_str(&line),
+            Err(err) => {
+                eprintln!("error: {err}");
+                return 1;
+            }
+        }
+        if buffer.trim().is_empty() {
+            buffer.clear();
+            continue;
+        }
+        let source = Source::from_string(format!("<repl:{}>", entry + 1), buffer.as_str());
+        if session::is_incomplete(source.clone()) {
+            continue;
+        }
+        buffer.clear();
+        entry += 1;
+        match session.compile(source, vm.program_mut()) {
+            Ok(Some(function)) => {
+                if let Err(err) = vm.call(function) {
+                    eprintln!("{err}");
+                }
+            }
+            Ok(None) => (),
+            Err(err) => {
+                eprintln!("error: {err}");
+                continue;
+            }
+        }
+        print_stack(&vm);
+    }
+    println!();
+    0
+}
+
+fn print_stack(vm: &Vm) {
+    let stack = vm.stack();
+    print!("<{}>", stack.len());
+    for &value in stack {
+        print!(" {}", vm.heap().inspect(value));
+    }
+    println!();
+}
@@ -0,0 +1,48 @@
+use celo::compiler::{source::Source, Compiler};
+use maquina::vm::Vm;
+
+/// Runs the `test!` blocks of a file whose names contain the filter, each on a fresh VM.
+pub fn test(path: &str, filter: Option<&str>) -> i32 {
+    let result = Source::load(path).and_then(|source| Compiler::new(source).compile_tests());
+    let (program, tests) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return 1;
+        }
+    };
+    let selected: Vec<_> = tests
+        .iter()
+        .filter(|test| filter.is_none_or(|filter| test.name.contains(filter)))
+        .collect();
+    let filtered = tests.len() - selected.len();
+    println!("running {} tests", selected.len());
+    let mut failures = Vec::new();
+    for test in &selected {
+        let mut vm = Vm::new(program.clone());
+        match vm.call(test.function as u32) {
+            Ok(()) => println!("test {} ... ok", test.name),
+            Err(err) => {
+                println!("test {} ... FAILED", test.name);
+                failures.push((&test.name, err));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, err) in &failures {
+            println!("\n---- {name} ----\n{err}");
+        }
+    }
+    let passed = selected.len() - failures.len();
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!(
+        "\ntest result: {status}. {passed} passed; {} failed; {filtered} filtered out",
+        failures.len()
+    );
+    if failures.is_empty() {
+        0
+    } else {
+        1
+    }
+}
@@ -0,0 +1,45 @@
+use std::{fmt::Write, rc::Rc};
+
+use celo::compiler::{error::Result, lexer::Lexer, source::Source};
+use serde_json::json;
+
+/// Prints the tokens of a file with their kind, position and text, one per line.
+pub fn tokens(path: &str, json: bool) -> i32 {
+    let result = Source::load(path).and_then(|source| render(&source, json));
+    match result {
+        Ok(output) => {
+            print!("{output}");
+            0
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            1
+        }
+    }
+}
+
+/// Renders the tokens of a source as aligned text or as one JSON object per line.
+pub fn render(source: &Rc<Source>, json: bool) -> Result<String> {
+    let mut output = String::new();
+    for token in Lexer::new(source.clone()) {
+        let token = token?;
+        let location = token.location;
+        let kind = token.kind.name();
+        let text = &source[location];
+        if json {
+            let token = json!({
+                "kind": kind,
+                "line": location.line,
+                "column": location.column,
+                "start": location.start,
+                "end": location.end,
+                "text": text,
+            });
+            _ = writeln!(output, "{token}");
+        } else {
+            let position = format!("{}:{}", location.line, location.column);
+            _ = writeln!(output, "{position:<9} {kind:<15} {text:?}");
+        }
+    }
+    Ok(output)
+}
@@ -0,0 +1,103 @@
+use std::rc::Rc;
+
+use celo::compiler::{
+    diagnostic::Diagnostic,
+    source::{MemoryFileProvider, Source},
+    Compiler,
+};
+use celo_cli::check::{render, MessageFormat};
+use serde_json::{json, Value};
+
+fn diagnostics(code: &str) -> Vec<Diagnostic> {
+    let source = Source::from_string("test.celo", code);
+    let result = Compiler::with_file_provider(source, Rc::new(MemoryFileProvider::new())).check();
+    result.err().iter().map(Diagnostic::from).collect()
+}
+
+#[test]
+fn renders_human_messages() {
+    let output = render(
+        &diagnostics("fn! twice {}\nfn! twice {}"),
+        MessageFormat::Human,
+    );
+    assert_eq!(
+        output,
+        "error[C0201]: test.celo:2:5: function is already defined at 1:5\n\
+         note: test.celo:1:5: first defined here\n"
+    );
+    assert_eq!(
+        render(&diagnostics("fn! main {}"), MessageFormat::Human),
+        ""
+    );
+}
+
+#[test]
+fn renders_json_objects() {
+    let output = render(&diagnostics("fn! main {\n  (1 2 ]\n}"), MessageFormat::Json);
+    let [line] = output.lines().collect::<Vec<_>>()[..] else {
+        panic!("expected one diagnostic per line");
+    };
+    let diagnostic: Value = serde_json::from_str(line).unwrap();
+    assert_eq!(diagnostic["severity"], "error");
+    assert_eq!(diagnostic["code"], "C0001");
+    assert_eq!(diagnostic["name"], "UnmatchedBracket");
+    assert_eq!(diagnostic["file"], "test.celo");
+    assert_eq!(diagnostic["range"], json!({ "start": 18, "end": 19 }));
+    assert_eq!(diagnostic["start"], json!({ "line": 2, "column": 8 }));
+    assert_eq!(diagnostic["end"], json!({ "line": 2, "column": 9 }));
+    let related = &diagnostic["related"][0];
+    assert_eq!(related["message"], "bracket opened here");
+    assert_eq!(related["file"], "test.celo");
+    assert_eq!(related["start"], json!({ "line": 2, "column": 3 }));
+
+    let output = render(
+        &diagnostics("import! \"missing.celo\""),
+        MessageFormat::Json,
+    );
+    let diagnostic: Value = serde_json::from_str(output.trim_end()).unwrap();
+    assert_eq!(diagnostic["code"], "C0301");
+    assert_eq!(diagnostic["range"], Value::Null);
+}
+
+#[test]
+fn renders_sarif_logs() {
+    let output = render(
+        &diagnostics("fn! twice {}\nfn! twice {}"),
+        MessageFormat::Sarif,
+    );
+    let log: Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(log["version"], "2.1.0");
+    let run = &log["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "celo");
+    assert_eq!(
+        run["tool"]["driver"]["rules"],
+        json!([{ "id": "C0201", "name": "DuplicateFunction" }])
+    );
+    let [result] = &run["results"].as_array().unwrap()[..] else {
+        panic!("expected one result");
+    };
+    assert_eq!(result["ruleId"], "C0201");
+    assert_eq!(result["level"], "error");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "test.celo");
+    assert_eq!(
+        location["region"],
+        json!({
+            "startLine": 2,
+            "startColumn": 5,
+            "endLine": 2,
+            "endColumn": 10,
+            "byteOffset": 17,
+            "byteLength": 5,
+        })
+    );
+    let related = &result["relatedLocations"][0];
+    assert_eq!(related["id"], 0);
+    assert_eq!(related["message"]["text"], "first defined here");
+    assert_eq!(related["physicalLocation"]["region"]["startLine"], 1);
+
+    let output = render(&[], MessageFormat::Sarif);
+    let log: Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(log["runs"][0]["results"], json!([]));
+    assert_eq!(log["runs"][0]["tool"]["driver"]["rules"], json!([]));
+}
@@ -0,0 +1,62 @@
+use celo::compiler::{error::Error, source::Source};
+use celo_cli::tokens::render;
+use serde_json::{json, Value};
+
+const CODE: &str = "fn! main {\n  .x \"hi\"\n}";
+
+#[test]
+fn renders_text() {
+    let source = Source::from_string("main.celo", CODE);
+    let output = render(&source, false).unwrap();
+    assert_eq!(
+        output,
+        "\
+1:1       BangIdentifier  \"fn!\"
+1:5       Identifier      \"main\"
+1:10      LeftCurly       \"{\"
+2:3       DotIdentifier   \".x\"
+2:6       String          \"\\\"hi\\\"\"
+3:1       RightCurly      \"}\"
+"
+    );
+}
+
+#[test]
+fn renders_json_lines() {
+    let source = Source::from_string("main.celo", CODE);
+    let output = render(&source, true).unwrap();
+    let tokens: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(tokens.len(), 6);
+    assert_eq!(
+        tokens[3],
+        json!({
+            "kind": "DotIdentifier",
+            "line": 2,
+            "column": 3,
+            "start": 13,
+            "end": 15,
+            "text": ".x",
+        })
+    );
+    let kinds: Vec<&str> = tokens.iter().map(|t| t["kind"].as_str().unwrap()).collect();
+    assert_eq!(
+        kinds,
+        [
+            "BangIdentifier",
+            "Identifier",
+            "LeftCurly",
+            "DotIdentifier",
+            "String",
+            "RightCurly"
+        ]
+    );
+}
+
+#[test]
+fn stops_at_lexer_errors() {
+    let source = Source::from_string("main.celo", "1 $ 2");
+    assert!(matches!(render(&source, true), Err(Error::Lexer(_))));
+}
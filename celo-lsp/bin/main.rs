@@ -0,0 +1,6 @@
+use std::{env::args, process::exit};
+
+fn main() {
+    let args: Vec<String> = args().skip(1).collect();
+    exit(celo_lsp::main(&args));
+}
@@ -0,0 +1,315 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    iter, mem,
+    rc::Rc,
+};
+
+use celo::compiler::{
+    effect::{self, StackEffect},
+    error::Error,
+    experimental, hir,
+    incremental::{Document, FunctionChange},
+    lexer::KEYWORDS,
+    lowering::{LowerMirStep, INTRINSICS},
+    mir,
+    parser::ParseHirStep,
+    source::{Location, Source, TokenKind},
+    Compiler,
+};
+
+/// Result of checking a document along with the modules it imports.
+pub struct Analysis {
+    pub source: Rc<Source>,
+    /// First error of checking the document, other than the errors of the document itself
+    pub error: Option<Error>,
+    /// Imported modules, with an empty module 0 in place of the document
+    hir: hir::Hir,
+    /// Stack effects of functions by module index and name
+    effects: HashMap<(usize, String), StackEffect>,
+    /// Whether the document was lowered without errors
+    checked: bool,
+    macros: Vec<String>,
+}
+
+/// What a name in the document refers to.
+pub enum Symbol<'a> {
+    Function {
+        /// Source that defines the function
+        source: &'a Rc<Source>,
+        function: &'a hir::Function,
+        effect: Option<StackEffect>,
+    },
+    Intrinsic {
+        name: &'a str,
+        effect: StackEffect,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionKind {
+    Function,
+    Macro,
+    Keyword,
+    Variable,
+}
+
+#[derive(Debug)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+}
+
+impl Analysis {
+    /// Parses the imports of a document and lowers them together with its functions and tests.
+    pub fn new(compiler: &Compiler, document: &mut Document) -> Self {
+        let source = document.source().clone();
+        let mut step = ParseHirStep::new(compiler, source.clone());
+        experimental::init(&mut step);
+        let mut macros: Vec<String> = step.root_macros().map(|name| format!("{name}!")).collect();
+        macros.sort();
+        let mut analysis = Self {
+            source: source.clone(),
+            error: None,
+            hir: hir::Hir {
+                modules: vec![Box::new(hir::Module::new(source))],
+            },
+            effects: HashMap::new(),
+            checked: false,
+            macros,
+        };
+        // Errors of the document are reported by the document
+        if document.error().is_some() {
+            return analysis;
+        }
+        match step.run_imports(document.imports().cloned().collect::<Vec<_>>()) {
+            Ok(hir) => analysis.hir = hir,
+            Err(err) => {
+                analysis.error = Some(err);
+                return analysis;
+            }
+        }
+        let hir = &mut analysis.hir;
+        let result = document.with_module(|module| {
+            mem::swap(&mut *hir.modules[0], module);
+            let result = LowerMirStep::new(hir)
+                .with_tests()
+                .run_incremental()
+                .map(|(mir, _)| effects(hir, &mir));
+            mem::swap(&mut *hir.modules[0], module);
+            result
+        });
+        match result {
+            Ok(effects) => {
+                analysis.effects = effects;
+                analysis.checked = true;
+            }
+            Err(err) => analysis.error = Some(err),
+        }
+        analysis
+    }
+
+    /// Brings the analysis up to date after edits of the document. The document is only checked
+    /// again if its functions or tests changed or the last check did not succeed.
+    pub fn update(
+        &mut self,
+        compiler: &Compiler,
+        document: &mut Document,
+        changes: &[FunctionChange],
+    ) {
+        if self.checked && changes.is_empty() && document.error().is_none() {
+            self.source = document.source().clone();
+            self.hir.modules[0].source = self.source.clone();
+            return;
+        }
+        *self = Self::new(compiler, document);
+    }
+
+    /// Returns the symbol referenced at the byte offset, either by a call in a function or test,
+    /// or by a function name.
+    pub fn symbol_at<'a>(
+        &'a self,
+        document: &'a Document,
+        offset: u32,
+    ) -> Option<(Location, Symbol<'a>)> {
+        for function in document.functions() {
+            if contains(function.name, offset) {
+                let symbol = self.function_symbol(document, 0, &self.source[function.name]);
+                return Some((function.name, symbol?));
+            }
+        }
+        for function in document.functions().chain(document.tests()) {
+            if let Some(call) = find_call(&function.body.code, offset) {
+                return Some((call, self.function_symbol(document, 0, &self.source[call])?));
+            }
+        }
+        None
+    }
+
+    /// Returns the functions of a module, which are kept by the document for module 0.
+    fn module_functions<'a>(
+        &'a self,
+        document: &'a Document,
+        module_index: usize,
+    ) -> Box<dyn Iterator<Item = &'a hir::Function> + 'a> {
+        match module_index {
+            0 => Box::new(document.functions()),
+            _ => Box::new(
+                self.hir.modules[module_index]
+                    .functions
+                    .iter()
+                    .map(|function| &**function),
+            ),
+        }
+    }
+
+    /// Resolves a function name the same way lowering does.
+    fn function_symbol<'a>(
+        &'a self,
+        document: &'a Document,
+        module_index: usize,
+        name: &'a str,
+    ) -> Option<Symbol<'a>> {
+        let modules = iter::once(module_index).chain(0..self.hir.modules.len());
+        for module_index in modules {
+            let source = &self.hir.modules[module_index].source;
+            let Some(function) = self
+                .module_functions(document, module_index)
+                .find(|function| &source[function.name] == name)
+            else {
+                continue;
+            };
+            return Some(Symbol::Function {
+                source,
+                function,
+                effect: self.effects.get(&(module_index, name.into())).copied(),
+            });
+        }
+        let &intrinsic = INTRINSICS.get(name)?;
+        Some(Symbol::Intrinsic {
+            name,
+            effect: intrinsic.into(),
+        })
+    }
+
+    /// Returns the functions defined in the document with their stack effects.
+    pub fn functions<'a>(
+        &self,
+        document: &'a Document,
+    ) -> Vec<(&'a hir::Function, Option<StackEffect>)> {
+        document
+            .functions()
+            .map(|function| {
+                let name = self.source[function.name].to_string();
+                (function, self.effects.get(&(0, name)).copied())
+            })
+            .collect()
+    }
+
+    pub fn completions(&self, document: &Document) -> Vec<Completion> {
+        let mut completions = Vec::new();
+        let mut functions = BTreeSet::new();
+        let mut variables = BTreeSet::new();
+        for (module_index, module) in self.hir.modules.iter().enumerate() {
+            for function in self.module_functions(document, module_index) {
+                let name = &module.source[function.name];
+                if functions.insert(name.to_string()) {
+                    let detail = match self.function_symbol(document, module_index, name) {
+                        Some(Symbol::Function {
+                            effect: Some(effect),
+                            ..
+                        }) => Some(format!("fn! {name} {effect}")),
+                        _ => Some(format!("fn! {name}")),
+                    };
+                    completions.push(Completion {
+                        label: name.into(),
+                        kind: CompletionKind::Function,
+                        detail,
+                    });
+                }
+            }
+        }
+        // Fall back to tokens so that completion keeps working while the document has errors
+        let mut after_fn = false;
+        for &token in document.tokens() {
+            let text = &self.source[token.location];
+            match token.kind {
+                TokenKind::Identifier if after_fn && functions.insert(text.into()) => {
+                    completions.push(Completion {
+                        label: text.into(),
+                        kind: CompletionKind::Function,
+                        detail: Some(format!("fn! {text}")),
+                    });
+                }
+                TokenKind::DotIdentifier => _ = variables.insert(text.to_string()),
+                _ => {}
+            }
+            after_fn = token.kind == TokenKind::BangIdentifier && text == "fn!";
+        }
+        for (&name, &intrinsic) in INTRINSICS.entries() {
+            if !functions.contains(name) {
+                completions.push(Completion {
+                    label: name.into(),
+                    kind: CompletionKind::Function,
+                    detail: Some(format!("intrinsic {}", StackEffect::from(intrinsic))),
+                });
+            }
+        }
+        completions.extend(self.macros.iter().map(|name| Completion {
+            label: name.clone(),
+            kind: CompletionKind::Macro,
+            detail: None,
+        }));
+        completions.extend(KEYWORDS.keys().map(|&keyword| Completion {
+            label: keyword.into(),
+            kind: CompletionKind::Keyword,
+            detail: None,
+        }));
+        completions.extend(variables.into_iter().map(|name| Completion {
+            label: name,
+            kind: CompletionKind::Variable,
+            detail: None,
+        }));
+        completions
+    }
+}
+
+/// Keys the stack effects of the functions of all modules, leaving out tests.
+fn effects(hir: &hir::Hir, mir: &mir::Mir) -> HashMap<(usize, String), StackEffect> {
+    let tests: HashSet<usize> = mir
+        .tests
+        .iter()
+        .map(|test| test.function - mir.first_function)
+        .collect();
+    effect::infer(mir)
+        .into_iter()
+        .zip(&mir.functions)
+        .enumerate()
+        .filter(|(index, _)| !tests.contains(index))
+        .filter_map(|(_, (effect, function))| {
+            let name = &hir.modules[function.module].source[function.name];
+            Some(((function.module, name.to_string()), effect?))
+        })
+        .collect()
+}
+
+fn contains(location: Location, offset: u32) -> bool {
+    location.start <= offset && offset <= location.end
+}
+
+fn find_call(nodes: &[hir::Node], offset: u32) -> Option<Location> {
+    nodes.iter().find_map(|node| {
+        if !contains(node.location, offset) {
+            return None;
+        }
+        match &node.kind {
+            hir::NodeKind::Call => Some(node.location),
+            hir::NodeKind::Group(group) => find_call(&group.nodes, offset),
+            hir::NodeKind::If(if_node) => find_call(&if_node.then_scope.code, offset)
+                .or_else(|| find_call(&if_node.else_scope.as_ref()?.code, offset)),
+            hir::NodeKind::Try(try_node) => find_call(&try_node.body.code, offset)
+                .or_else(|| find_call(&try_node.handler.code, offset)),
+            _ => None,
+        }
+    })
+}
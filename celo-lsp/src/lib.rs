@@ -0,0 +1,23 @@
+use std::io::{stdin, stdout};
+
+pub mod analysis;
+pub mod protocol;
+pub mod server;
+
+const USAGE: &str = "usage: celo-lsp [--stdio]";
+
+/// Runs the language server on stdin and stdout until the client asks it to exit.
+pub fn main(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg != "--stdio") {
+        eprintln!("{USAGE}");
+        return 1;
+    }
+    let mut server = server::Server::default();
+    match server.run(&mut stdin().lock(), &mut stdout().lock()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {err}");
+            1
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+use celo::compiler::{
+    incremental::{Document, TextEdit},
+    source::{ColumnMode, DiskFileProvider, LineColumn, Location, MemoryFileProvider, Source},
+    Compiler,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    analysis::{Analysis, CompletionKind, Symbol},
+    protocol::{path_to_uri, read_message, uri_to_path, write_message},
+};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Language server state: the open documents and their latest analysis.
+#[derive(Default)]
+pub struct Server {
+    /// Open documents by path, shadowing the files on disk
+    documents: HashMap<Rc<str>, Document>,
+    analyses: HashMap<Rc<str>, Analysis>,
+    shutdown: bool,
+    exit: bool,
+}
+
+impl Server {
+    /// Serves messages until the client exits. Returns the process exit code.
+    pub fn run(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<i32> {
+        while let Some(message) = read_message(reader)? {
+            for response in self.handle(&message) {
+                write_message(writer, &response)?;
+            }
+            if self.exit {
+                break;
+            }
+        }
+        Ok(if self.shutdown { 0 } else { 1 })
+    }
+
+    /// Handles a request or notification and returns the messages to send back.
+    ///
+    /// Responses from the client are ignored.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let Some(method) = message["method"].as_str() else {
+            return Vec::new();
+        };
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            return self.notification(method, params);
+        };
+        let result = match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            "textDocument/completion" => self.completion(params),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{method}`"))),
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        vec![response]
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let path: Rc<str> = uri_to_path(uri).into();
+        match method {
+            "exit" => self.exit = true,
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                let source = Source::from_string(path.clone(), text);
+                let compiler = self.compiler(source.clone());
+                let mut document = Document::new(&compiler, source);
+                let analysis = Analysis::new(&compiler, &mut document);
+                self.documents.insert(path.clone(), document);
+                self.analyses.insert(path.clone(), analysis);
+                return vec![self.diagnostics(uri, &path)];
+            }
+            "textDocument/didChange" => {
+                let Some(mut document) = self.documents.remove(&path) else {
+                    return Vec::new();
+                };
+                let mut changes = Vec::new();
+                let mut replaced = false;
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    let compiler = self.compiler(document.source().clone());
+                    let text = change["text"].as_str().unwrap_or_default();
+                    // Changes without a range replace the whole document
+                    let Some(range) = change.get("range") else {
+                        let source = Source::from_string(path.clone(), text);
+                        document = Document::new(&compiler, source);
+                        replaced = true;
+                        continue;
+                    };
+                    let start = offset(document.source(), &range["start"]);
+                    let end = offset(document.source(), &range["end"]).max(start);
+                    let edit = TextEdit {
+                        start,
+                        end,
+                        text: text.into(),
+                    };
+                    changes.extend(document.edit(&compiler, &edit).changes);
+                }
+                let compiler = self.compiler(document.source().clone());
+                match self.analyses.get_mut(&path) {
+                    Some(analysis) if !replaced => {
+                        analysis.update(&compiler, &mut document, &changes)
+                    }
+                    _ => {
+                        let analysis = Analysis::new(&compiler, &mut document);
+                        self.analyses.insert(path.clone(), analysis);
+                    }
+                }
+                self.documents.insert(path.clone(), document);
+                return vec![self.diagnostics(uri, &path)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&path);
+                self.analyses.remove(&path);
+                return vec![diagnostics_notification(uri, Vec::new())];
+            }
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    /// Creates a compiler that reads the open documents instead of the files on disk.
+    fn compiler(&self, source: Rc<Source>) -> Compiler {
+        let mut file_provider = MemoryFileProvider::with_fallback(DiskFileProvider);
+        for (path, document) in &self.documents {
+            file_provider.insert(path.clone(), document.source().content.clone());
+        }
+        Compiler::with_file_provider(source, Rc::new(file_provider))
+    }
+
+    /// Returns the diagnostics of a document, which are its own first error or else the first
+    /// error of its analysis.
+    fn diagnostics(&self, uri: &str, path: &Rc<str>) -> Value {
+        let source = self.documents[path].source();
+        let error = self.documents[path].error();
+        let mut diagnostics = Vec::new();
+        if let Some(err) = error.or(self.analyses[path].error.as_ref()) {
+            let in_document = err.path() == path;
+            let range = match err.location() {
+                Some(location) if in_document => range(source, location),
+                _ => range(source, Location::default()),
+            };
+            let message = if in_document {
+                err.message()
+            } else {
+                err.to_string()
+            };
+            diagnostics.push(json!({
+                "range": range,
+                "severity": 1,
+                "code": err.code(),
+                "source": "celo",
+                "message": message,
+            }));
+        }
+        diagnostics_notification(uri, diagnostics)
+    }
+
+    /// Returns the document, its analysis and the byte offset of the position in the params.
+    fn position(&self, params: &Value) -> Result<(&Document, &Analysis, u32), (i64, String)> {
+        let (document, analysis) = self.analysis(params)?;
+        let position = &params["position"];
+        let (Some(line), Some(character)) =
+            (position["line"].as_u64(), position["character"].as_u64())
+        else {
+            return Err((INVALID_PARAMS, "missing position".into()));
+        };
+        let position = LineColumn {
+            line: line as u32 + 1,
+            column: character as u32 + 1,
+        };
+        let offset = analysis
+            .source
+            .offset(position, ColumnMode::Utf16)
+            .ok_or((INVALID_PARAMS, "position out of range".into()))?;
+        Ok((document, analysis, offset))
+    }
+
+    fn analysis(&self, params: &Value) -> Result<(&Document, &Analysis), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let path = uri_to_path(uri);
+        let (Some(document), Some(analysis)) = (
+            self.documents.get(path.as_str()),
+            self.analyses.get(path.as_str()),
+        ) else {
+            return Err((INVALID_PARAMS, format!("document `{uri}` is not open")));
+        };
+        Ok((document, analysis))
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, analysis, offset) = self.position(params)?;
+        let Some((
+            _,
+            Symbol::Function {
+                source, function, ..
+            },
+        )) = analysis.symbol_at(document, offset)
+        else {
+            return Ok(Value::Null);
+        };
+        Ok(json!({
+            "uri": path_to_uri(&source.path),
+            "range": range(source, function.name),
+        }))
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, analysis, offset) = self.position(params)?;
+        let Some((location, symbol)) = analysis.symbol_at(document, offset) else {
+            return Ok(Value::Null);
+        };
+        let doc = match &symbol {
+            Symbol::Function { function, .. } => function.doc.as_deref(),
+            Symbol::Intrinsic { .. } => None,
+        };
+        let signature = match symbol {
+            Symbol::Function {
+                source,
+                function,
+                effect: Some(effect),
+            } => format!("fn! {} {effect}", &source[function.name]),
+            Symbol::Function {
+                source, function, ..
+            } => format!("fn! {}", &source[function.name]),
+            Symbol::Intrinsic { name, effect } => format!("{name} {effect}"),
+        };
+        let mut contents = format!("```celo\n{signature}\n```");
+        if let Some(doc) = doc {
+            contents.push_str("\n\n");
+            contents.push_str(doc);
+        }
+        Ok(json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": range(&analysis.source, location),
+        }))
+    }
+
+    fn document_symbols(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, analysis) = self.analysis(params)?;
+        let symbols: Vec<Value> = analysis
+            .functions(document)
+            .into_iter()
+            .map(|(function, effect)| {
+                let mut symbol = json!({
+                    "name": &analysis.source[function.name],
+                    "kind": 12,
+                    "range": range(&analysis.source, function.location),
+                    "selectionRange": range(&analysis.source, function.name),
+                });
+                if let Some(effect) = effect {
+                    symbol["detail"] = effect.to_string().into();
+                }
+                symbol
+            })
+            .collect();
+        Ok(symbols.into())
+    }
+
+    fn completion(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, analysis) = self.analysis(params)?;
+        let items: Vec<Value> = analysis
+            .completions(document)
+            .into_iter()
+            .map(|completion| {
+                let kind = match completion.kind {
+                    CompletionKind::Function => 3,
+                    CompletionKind::Variable => 6,
+                    CompletionKind::Keyword => 14,
+                    CompletionKind::Macro => 15,
+                };
+                let mut item = json!({ "label": completion.label, "kind": kind });
+                if let Some(detail) = completion.detail {
+                    item["detail"] = detail.into();
+                }
+                item
+            })
+            .collect();
+        Ok(items.into())
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 2,
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "documentSymbolProvider": true,
+            "completionProvider": { "triggerCharacters": ["."] },
+        },
+        "serverInfo": { "name": "celo-lsp", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn diagnostics_notification(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Converts a zero-based LSP position into a byte offset, clamped to the end of the source.
+fn offset(source: &Source, position: &Value) -> u32 {
+    let position = LineColumn {
+        line: position["line"].as_u64().unwrap_or_default() as u32 + 1,
+        column: position["character"].as_u64().unwrap_or_default() as u32 + 1,
+    };
+    source
+        .offset(position, ColumnMode::Utf16)
+        .unwrap_or(source.content.len() as u32)
+}
+
+/// Converts a location into a zero-based LSP range.
+fn range(source: &Source, location: Location) -> Value {
+    let (start, end) = source.range(location, ColumnMode::Utf16);
+    json!({
+        "start": { "line": start.line - 1, "character": start.column - 1 },
+        "end": { "line": end.line - 1, "character": end.column - 1 },
+    })
+}
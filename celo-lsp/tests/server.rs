@@ -0,0 +1,281 @@
+use celo_lsp::server::Server;
+use serde_json::{json, Value};
+
+const URI: &str = "file:///project/main.celo";
+
+fn open(server: &mut Server, text: &str) -> Value {
+    open_uri(server, URI, text)
+}
+
+fn open_uri(server: &mut Server, uri: &str, text: &str) -> Value {
+    let mut messages = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {
+            "textDocument": { "uri": uri, "languageId": "celo", "version": 1, "text": text },
+        },
+    }));
+    messages.pop().expect("diagnostics")
+}
+
+fn request(server: &mut Server, method: &str, line: u32, character: u32) -> Value {
+    let mut messages = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": {
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        },
+    }));
+    messages.pop().expect("response")["result"].take()
+}
+
+#[test]
+fn publishes_diagnostics() {
+    let mut server = Server::default();
+    let diagnostics = open(&mut server, "fn! main {\n  1 frobnicate\n}");
+    assert_eq!(diagnostics["method"], "textDocument/publishDiagnostics");
+    let diagnostic = &diagnostics["params"]["diagnostics"][0];
+    assert_eq!(diagnostic["message"], "unknown function");
+    assert_eq!(
+        diagnostic["range"],
+        json!({
+            "start": { "line": 1, "character": 4 },
+            "end": { "line": 1, "character": 14 },
+        })
+    );
+
+    let diagnostics = open(&mut server, "fn! main { 1 print }");
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+}
+
+fn change(server: &mut Server, changes: Value) -> Value {
+    let mut messages = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": changes,
+        },
+    }));
+    messages.pop().expect("diagnostics")
+}
+
+#[test]
+fn applies_incremental_changes() {
+    let mut server = Server::default();
+    let diagnostics = open(&mut server, "fn! main { \"🦀\" prnt }");
+    assert_eq!(
+        diagnostics["params"]["diagnostics"][0]["message"],
+        "unknown function"
+    );
+    // Columns count UTF-16 code units, of which the crab takes two
+    let diagnostics = change(
+        &mut server,
+        json!([{
+            "range": {
+                "start": { "line": 0, "character": 16 },
+                "end": { "line": 0, "character": 20 },
+            },
+            "text": "print",
+        }]),
+    );
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    let diagnostics = change(
+        &mut server,
+        json!([
+            {
+                "range": {
+                    "start": { "line": 0, "character": 23 },
+                    "end": { "line": 0, "character": 23 },
+                },
+                "text": "\nfn! helper { 1 frobnicate }",
+            },
+            {
+                "range": {
+                    "start": { "line": 1, "character": 4 },
+                    "end": { "line": 1, "character": 10 },
+                },
+                "text": "square",
+            },
+        ]),
+    );
+    let diagnostic = &diagnostics["params"]["diagnostics"][0];
+    assert_eq!(diagnostic["message"], "unknown function");
+    assert_eq!(
+        diagnostic["range"],
+        json!({
+            "start": { "line": 1, "character": 15 },
+            "end": { "line": 1, "character": 25 },
+        })
+    );
+    let symbols = request(&mut server, "textDocument/documentSymbol", 0, 0);
+    let names: Vec<&str> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| symbol["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["main", "square"]);
+
+    let diagnostics = change(&mut server, json!([{ "text": "fn! main { 1 print }" }]));
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn checks_tests_and_keeps_results_for_unchanged_functions() {
+    let mut server = Server::default();
+    let diagnostics = open(&mut server, "fn! inc { 1 + }\ntest! it { 1 inc nope }");
+    let diagnostic = &diagnostics["params"]["diagnostics"][0];
+    assert_eq!(diagnostic["message"], "unknown function");
+    assert_eq!(
+        diagnostic["range"]["start"],
+        json!({ "line": 1, "character": 17 })
+    );
+
+    let diagnostics = change(
+        &mut server,
+        json!([{
+            "range": {
+                "start": { "line": 1, "character": 17 },
+                "end": { "line": 1, "character": 21 },
+            },
+            "text": "2 = assert",
+        }]),
+    );
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    // Blank lines change no function, so the stack effects are kept
+    let diagnostics = change(
+        &mut server,
+        json!([{
+            "range": {
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": 0, "character": 0 },
+            },
+            "text": "\n\n",
+        }]),
+    );
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+    let hover = request(&mut server, "textDocument/hover", 3, 13);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```celo\nfn! inc ( 1 -- 1 )\n```"
+    );
+    assert_eq!(
+        hover["range"]["start"],
+        json!({ "line": 3, "character": 13 })
+    );
+}
+
+#[test]
+fn resolves_imports_from_open_documents() {
+    let mut server = Server::default();
+    let lib = "file:///project/lib.celo";
+    open_uri(&mut server, lib, "fn! square { dup * }");
+    let diagnostics = open(
+        &mut server,
+        "import! \"lib.celo\"\nfn! main { 3 square print }",
+    );
+    assert_eq!(diagnostics["params"]["diagnostics"], json!([]));
+
+    let definition = request(&mut server, "textDocument/definition", 1, 14);
+    assert_eq!(definition["uri"], lib);
+    let hover = request(&mut server, "textDocument/hover", 1, 14);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```celo\nfn! square ( 1 -- 1 )\n```"
+    );
+}
+
+#[test]
+fn finds_definitions_and_hovers() {
+    let mut server = Server::default();
+    open(
+        &mut server,
+        "fn! square { dup * }\nfn! main { 3 square print }",
+    );
+
+    let definition = request(&mut server, "textDocument/definition", 1, 14);
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(
+        definition["range"],
+        json!({
+            "start": { "line": 0, "character": 4 },
+            "end": { "line": 0, "character": 10 },
+        })
+    );
+
+    let hover = request(&mut server, "textDocument/hover", 1, 14);
+    assert_eq!(
+        hover["contents"]["value"],
+        "```celo\nfn! square ( 1 -- 1 )\n```"
+    );
+    let hover = request(&mut server, "textDocument/hover", 0, 14);
+    assert_eq!(hover["contents"]["value"], "```celo\ndup ( 1 -- 2 )\n```");
+}
+
+#[test]
+fn lists_symbols_and_completions() {
+    let mut server = Server::default();
+    open(
+        &mut server,
+        "fn! inc { 1 + }\nfn! main { 2 -> .x .x inc print }",
+    );
+
+    let symbols = request(&mut server, "textDocument/documentSymbol", 0, 0);
+    let names: Vec<&str> = symbols
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| symbol["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["inc", "main"]);
+    assert_eq!(symbols[0]["detail"], "( 1 -- 1 )");
+
+    let completions = request(&mut server, "textDocument/completion", 1, 0);
+    let labels: Vec<&str> = completions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+    for label in ["inc", "main", "dup", "fn!", "import!", "if", ".x"] {
+        assert!(labels.contains(&label), "missing completion {label}");
+    }
+}
+
+#[test]
+fn exits_after_shutdown() {
+    let mut input = Vec::new();
+    for message in [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ] {
+        celo_lsp::protocol::write_message(&mut input, &message).unwrap();
+    }
+    let mut output = Vec::new();
+    let code = Server::default()
+        .run(&mut input.as_slice(), &mut output)
+        .unwrap();
+    assert_eq!(code, 0);
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("\"definitionProvider\":true"));
+}
+
+#[test]
+fn ignores_client_responses() {
+    let mut server = Server::default();
+    let messages = server.handle(&json!({ "jsonrpc": "2.0", "id": 1, "result": null }));
+    assert!(messages.is_empty());
+}
+
+#[test]
+fn rejects_truncated_messages() {
+    let input = b"Content-Length: 18446744073709551615\r\n\r\n{}";
+    let err = celo_lsp::protocol::read_message(&mut input.as_slice()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,20 @@
+//! Lexes arbitrary text, which has to end in a token stream or an error without panicking.
+
+#![no_main]
+
+use celo::compiler::{lexer::Lexer, source::Source};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|content: &str| {
+    let source = Source::from_string("fuzz.celo", content);
+    for mut lexer in [
+        Lexer::new(source.clone()),
+        Lexer::with_comments(source.clone()),
+    ] {
+        while let Ok(Some(token)) = lexer.peek_token() {
+            // Panics if the location is out of bounds or splits a character
+            _ = &source[token.location];
+            lexer.consume_token().expect("token was peeked");
+        }
+    }
+});
@@ -0,0 +1,21 @@
+//! Parses and lowers arbitrary text, which has to succeed or return an error without panicking.
+
+#![no_main]
+
+use std::rc::Rc;
+
+use celo::compiler::{
+    lowering::LowerMirStep,
+    source::{MemoryFileProvider, Source},
+    Compiler,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|content: &str| {
+    let source = Source::from_string("fuzz.celo", content);
+    // Imports resolve to nothing instead of the file system
+    let mut compiler = Compiler::with_file_provider(source, Rc::new(MemoryFileProvider::new()));
+    if let Ok(hir) = compiler.parse() {
+        _ = LowerMirStep::new(&hir).run();
+    }
+});
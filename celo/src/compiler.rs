@@ -1,44 +1,126 @@
-use std::rc::Rc;
+use std::{mem, rc::Rc};
 
-use self::{error::Result, parser::ParseHirStep, source::Source};
+use maquina::vm::program::Program;
 
+use self::{
+    error::Result,
+    hir::Hir,
+    lexer::KeywordTable,
+    lowering::LowerMirStep,
+    mir::Test,
+    parser::ParseHirStep,
+    source::{DiskFileProvider, FileProvider, Source},
+};
+
+pub mod codegen;
+pub mod diagnostic;
+pub mod effect;
 pub mod error;
 pub mod hir;
+pub mod incremental;
 pub mod lexer;
+pub mod lowering;
 pub mod mir;
 pub mod parser;
+pub mod session;
 pub mod source;
+pub mod syntax;
 
 pub struct Compiler {
     main_source: Rc<Source>,
+    file_provider: Rc<dyn FileProvider>,
+    keywords: Rc<KeywordTable>,
 }
 
 impl Compiler {
     pub fn new(main_source: Rc<Source>) -> Self {
-        Self { main_source }
+        Self::with_file_provider(main_source, Rc::new(DiskFileProvider))
+    }
+
+    /// Creates a compiler that loads imported sources through the given provider.
+    pub fn with_file_provider(
+        main_source: Rc<Source>,
+        file_provider: Rc<dyn FileProvider>,
+    ) -> Self {
+        Self {
+            main_source,
+            file_provider,
+            keywords: Rc::default(),
+        }
     }
 
-    pub fn compile(&mut self) -> Result<()> {
+    pub fn file_provider(&self) -> &dyn FileProvider {
+        &*self.file_provider
+    }
+
+    pub fn keywords(&self) -> &Rc<KeywordTable> {
+        &self.keywords
+    }
+
+    /// Returns the keyword table to register keywords for this compilation.
+    pub fn keywords_mut(&mut self) -> &mut KeywordTable {
+        Rc::make_mut(&mut self.keywords)
+    }
+
+    /// Parses the main source and everything it imports.
+    pub fn parse(&mut self) -> Result<Hir> {
         let mut hir_step = ParseHirStep::new(self, self.main_source.clone());
         experimental::init(&mut hir_step);
-        let hir = hir_step.run()?;
-        println!("{hir:#?}");
-        // todo - implement
+        hir_step.run()
+    }
+
+    /// Compiles the `test!` blocks along with all functions. No `main` function is required.
+    pub fn compile_tests(&mut self) -> Result<(Program, Vec<Test>)> {
+        let hir = self.parse()?;
+        let (mut mir, _) = LowerMirStep::new(&hir).with_tests().run_incremental()?;
+        let tests = mem::take(&mut mir.tests);
+        Ok((codegen::generate(&mir), tests))
+    }
+
+    /// Parses and lowers the main source and its tests without generating code. No `main` function
+    /// is required.
+    pub fn check(&mut self) -> Result<()> {
+        let hir = self.parse()?;
+        LowerMirStep::new(&hir).with_tests().run_incremental()?;
         Ok(())
     }
+
+    pub fn compile(&mut self) -> Result<Program> {
+        let hir = self.parse()?;
+        let mir = LowerMirStep::new(&hir).run()?;
+        Ok(codegen::generate(&mir))
+    }
 }
 
 pub mod experimental {
-    use super::{error::Result, hir, parser::ParseHirStep, source::TokenKind};
+    use super::{error::Result, hir, lexer, parser::ParseHirStep, source::TokenKind};
 
     pub fn init(step: &mut ParseHirStep) {
         step.add_root_macro("fn", macro_fn);
+        step.add_root_macro("import", macro_import);
+        step.add_root_macro("test", macro_test);
     }
 
     fn macro_fn(step: &mut ParseHirStep) -> Result<()> {
+        let doc = step.take_doc_comment();
         let name = step.expect_token(TokenKind::Identifier)?.location;
         let scope = step.parse_scope()?;
-        step.add_function(hir::Function::new(name.span_to(scope.end), name, scope));
+        let mut function = hir::Function::new(name.span_to(scope.end), name, scope);
+        function.doc = doc;
+        step.add_function(function);
         Ok(())
     }
+
+    fn macro_test(step: &mut ParseHirStep) -> Result<()> {
+        let name = step.expect_token(TokenKind::Identifier)?.location;
+        let scope = step.parse_scope()?;
+        step.add_test(hir::Function::new(name.span_to(scope.end), name, scope));
+        Ok(())
+    }
+
+    fn macro_import(step: &mut ParseHirStep) -> Result<()> {
+        let path = step.expect_token(TokenKind::String)?.location;
+        let path = lexer::unescape(&step.lexer.source()[path]);
+        step.import(&path)
+    }
 }
@@ -0,0 +1,82 @@
+use maquina::vm::program::{DebugInfo, Function, Instruction, Program};
+
+use super::{
+    mir,
+    source::{Location, Source},
+};
+
+/// Translates the MIR into a program for the maquina VM.
+pub fn generate(mir: &mir::Mir) -> Program {
+    Program {
+        functions: generate_functions(mir),
+        globals: 0,
+        entry: mir.entry.map(|entry| entry as u32),
+    }
+}
+
+/// Translates the functions of the MIR, to be placed at [mir::Mir::first_function].
+pub fn generate_functions(mir: &mir::Mir) -> Vec<Function> {
+    mir.functions
+        .iter()
+        .map(|function| {
+            let source = &mir.modules[function.module].source;
+            generate_code(
+                &source[function.name],
+                source,
+                function.location,
+                &function.body,
+            )
+        })
+        .collect()
+}
+
+/// Translates code into a function. The location is used for the implicit return.
+pub fn generate_code(
+    name: &str,
+    source: &Source,
+    location: Location,
+    body: &mir::Code,
+) -> Function {
+    let mut code: Vec<Instruction> = body
+        .instructions
+        .iter()
+        .map(|instruction| match &instruction.kind {
+            mir::InstructionKind::Integer(integer) => Instruction::Integer(*integer),
+            mir::InstructionKind::Float(float) => Instruction::Float(*float),
+            mir::InstructionKind::String(string) => Instruction::String(string.clone()),
+            mir::InstructionKind::Load(local) => Instruction::Load(*local),
+            mir::InstructionKind::Store(local) => Instruction::Store(*local),
+            mir::InstructionKind::LoadGlobal(global) => Instruction::LoadGlobal(*global),
+            mir::InstructionKind::StoreGlobal(global) => Instruction::StoreGlobal(*global),
+            mir::InstructionKind::Call {
+                function,
+                tail: false,
+            } => Instruction::Call(*function as u32),
+            mir::InstructionKind::Call {
+                function,
+                tail: true,
+            } => Instruction::TailCall(*function as u32),
+            mir::InstructionKind::Intrinsic(intrinsic) => Instruction::Intrinsic(*intrinsic),
+            mir::InstructionKind::Jump(target) => Instruction::Jump(*target as u32),
+            mir::InstructionKind::JumpIfZero(target) => Instruction::JumpIfZero(*target as u32),
+            mir::InstructionKind::Try(handler) => Instruction::Try(*handler as u32),
+            mir::InstructionKind::EndTry => Instruction::EndTry,
+        })
+        .collect();
+    code.push(Instruction::Return);
+    let mut positions: Vec<_> = body
+        .instructions
+        .iter()
+        .map(|instruction| (instruction.location.line, instruction.location.column))
+        .collect();
+    positions.push((location.line, location.column));
+    Function {
+        name: name.into(),
+        locals: body.locals,
+        code,
+        debug: Some(DebugInfo {
+            path: source.path.clone(),
+            positions,
+        }),
+    }
+}
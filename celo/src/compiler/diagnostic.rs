@@ -0,0 +1,101 @@
+use std::rc::Rc;
+
+use super::{
+    error::Error,
+    lowering::LoweringErrorKind,
+    parser::ParserErrorKind,
+    source::{ColumnMode, LineColumn, Location, Source},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// Place in a file, with the byte range and the 1-based positions of both ends.
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub path: Rc<str>,
+    pub location: Location,
+    pub start: LineColumn,
+    pub end: LineColumn,
+}
+
+impl Span {
+    pub fn new(source: &Source, location: Location) -> Self {
+        let (start, end) = source.range(location, ColumnMode::Chars);
+        Self {
+            path: source.path.clone(),
+            location,
+            start,
+            end,
+        }
+    }
+}
+
+/// Other place that helps to understand a diagnostic, like an earlier definition.
+#[derive(Clone, Debug)]
+pub struct Related {
+    pub message: String,
+    pub span: Span,
+}
+
+/// Compiler message in a form that tools can consume.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable code of the kind of error, like `C0001`
+    pub code: &'static str,
+    /// Name of the kind of error, like `UnmatchedBracket`
+    pub name: &'static str,
+    pub message: String,
+    pub path: Rc<str>,
+    /// Where the problem is, or `None` if it concerns the whole file
+    pub span: Option<Span>,
+    pub related: Vec<Related>,
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(err: &Error) -> Self {
+        let span = |location| err.source().map(|source| Span::new(source, location));
+        let related = |message: &str, location| {
+            span(location).map(|span| Related {
+                message: message.into(),
+                span,
+            })
+        };
+        let related = match err {
+            Error::Parser(err) => match err.kind() {
+                ParserErrorKind::UnmatchedBracket {
+                    opening_bracket, ..
+                } => related("bracket opened here", *opening_bracket),
+                _ => None,
+            },
+            Error::Lowering(err) => match err.kind() {
+                LoweringErrorKind::DuplicateFunction { previous }
+                | LoweringErrorKind::DuplicateTest { previous } => {
+                    related("first defined here", *previous)
+                }
+                _ => None,
+            },
+            Error::Source(_) | Error::Lexer(_) => None,
+        };
+        Self {
+            severity: Severity::Error,
+            code: err.code(),
+            name: err.name(),
+            message: err.message(),
+            path: err.path().clone(),
+            span: err.location().and_then(span),
+            related: related.into_iter().collect(),
+        }
+    }
+}
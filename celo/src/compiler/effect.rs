@@ -0,0 +1,121 @@
+use std::fmt;
+
+use maquina::vm::intrinsic::Intrinsic;
+
+use super::mir;
+
+/// Number of values a piece of code pops from and pushes onto the stack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StackEffect {
+    pub inputs: u32,
+    pub outputs: u32,
+}
+
+impl StackEffect {
+    pub fn new(inputs: u32, outputs: u32) -> Self {
+        Self { inputs, outputs }
+    }
+}
+
+impl From<Intrinsic> for StackEffect {
+    fn from(intrinsic: Intrinsic) -> Self {
+        let (inputs, outputs) = intrinsic.stack_effect();
+        Self::new(inputs, outputs)
+    }
+}
+
+impl fmt::Display for StackEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "( {} -- {} )", self.inputs, self.outputs)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Pending,
+    InProgress,
+    Done(Option<StackEffect>),
+}
+
+/// Infers the stack effects of all functions of the MIR, indexed like `mir.functions`.
+///
+/// The effect is `None` if it depends on the path taken through the function, on recursion
+/// or on functions of earlier compilations.
+pub fn infer(mir: &mir::Mir) -> Vec<Option<StackEffect>> {
+    let mut states = vec![State::Pending; mir.functions.len()];
+    (0..mir.functions.len())
+        .map(|index| infer_function(mir, &mut states, index))
+        .collect()
+}
+
+fn infer_function(mir: &mir::Mir, states: &mut [State], index: usize) -> Option<StackEffect> {
+    match states[index] {
+        State::Pending => {}
+        State::InProgress => return None,
+        State::Done(effect) => return effect,
+    }
+    states[index] = State::InProgress;
+    let effect = infer_code(&mir.functions[index].body, |function| {
+        let index = function.checked_sub(mir.first_function)?;
+        infer_function(mir, states, index)
+    });
+    states[index] = State::Done(effect);
+    effect
+}
+
+/// Infers the stack effect of a body by following every path through its jumps.
+fn infer_code(
+    code: &mir::Code,
+    mut call: impl FnMut(usize) -> Option<StackEffect>,
+) -> Option<StackEffect> {
+    let len = code.instructions.len();
+    // Stack depth relative to the start when reaching each instruction, plus the return
+    let mut depths: Vec<Option<i64>> = vec![None; len + 1];
+    let mut lowest = 0;
+    let mut pending = vec![(0, 0)];
+    while let Some((pc, depth)) = pending.pop() {
+        match depths[pc] {
+            Some(known) if known == depth => continue,
+            Some(_) => return None,
+            None => depths[pc] = Some(depth),
+        }
+        if pc == len {
+            continue;
+        }
+        let (effect, targets) = match code.instructions[pc].kind {
+            mir::InstructionKind::Integer(_)
+            | mir::InstructionKind::Float(_)
+            | mir::InstructionKind::String(_)
+            | mir::InstructionKind::Load(_)
+            | mir::InstructionKind::LoadGlobal(_) => (StackEffect::new(0, 1), None),
+            mir::InstructionKind::Store(_) | mir::InstructionKind::StoreGlobal(_) => {
+                (StackEffect::new(1, 0), None)
+            }
+            mir::InstructionKind::Call { function, .. } => (call(function)?, None),
+            // Control never continues after a throw
+            mir::InstructionKind::Intrinsic(Intrinsic::Throw) => {
+                lowest = lowest.min(depth - 1);
+                continue;
+            }
+            mir::InstructionKind::Intrinsic(intrinsic) => (intrinsic.into(), None),
+            mir::InstructionKind::Jump(target) => {
+                pending.push((target, depth));
+                continue;
+            }
+            mir::InstructionKind::JumpIfZero(target) => {
+                (StackEffect::new(1, 0), Some((target, depth - 1)))
+            }
+            // The handler starts with the stack of the try and the error on top
+            mir::InstructionKind::Try(target) => {
+                (StackEffect::new(0, 0), Some((target, depth + 1)))
+            }
+            mir::InstructionKind::EndTry => (StackEffect::new(0, 0), None),
+        };
+        let popped = depth - effect.inputs as i64;
+        lowest = lowest.min(popped);
+        pending.push((pc + 1, popped + effect.outputs as i64));
+        pending.extend(targets);
+    }
+    let end = depths[len]?;
+    Some(StackEffect::new((-lowest) as u32, (end - lowest) as u32))
+}
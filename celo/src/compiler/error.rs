@@ -1,4 +1,11 @@
-use super::{lexer::LexerError, parser::ParserError, source::SourceError};
+use std::{fmt, rc::Rc};
+
+use super::{
+    lexer::{LexerError, LexerErrorKind},
+    lowering::{LoweringError, LoweringErrorKind},
+    parser::{ParserError, ParserErrorKind},
+    source::{Location, Source, SourceError, SourceErrorKind},
+};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -7,4 +14,172 @@ pub enum Error {
     Source(Box<SourceError>),
     Lexer(Box<LexerError>),
     Parser(Box<ParserError>),
+    Lowering(Box<LoweringError>),
+}
+
+impl Error {
+    /// Returns the path of the file the error occurred in.
+    pub fn path(&self) -> &Rc<str> {
+        match self {
+            Error::Source(err) => err.path(),
+            Error::Lexer(err) => &err.source().path,
+            Error::Parser(err) => &err.source().path,
+            Error::Lowering(err) => &err.source().path,
+        }
+    }
+
+    /// Returns the source the error occurred in, unless it could not be loaded.
+    pub fn source(&self) -> Option<&Rc<Source>> {
+        match self {
+            Error::Source(_) => None,
+            Error::Lexer(err) => Some(err.source()),
+            Error::Parser(err) => Some(err.source()),
+            Error::Lowering(err) => Some(err.source()),
+        }
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Error::Source(_) => None,
+            Error::Lexer(err) => Some(err.location()),
+            Error::Parser(err) => err.location(),
+            Error::Lowering(err) => err.location(),
+        }
+    }
+
+    /// Returns the stable code of the kind of error, like `C0001`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Source(err) => err.kind().code(),
+            Error::Lexer(err) => err.kind().code(),
+            Error::Parser(err) => err.kind().code(),
+            Error::Lowering(err) => err.kind().code(),
+        }
+    }
+
+    /// Returns the name of the kind of error, like `UnmatchedBracket`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Error::Source(err) => err.kind().name(),
+            Error::Lexer(err) => err.kind().name(),
+            Error::Parser(err) => err.kind().name(),
+            Error::Lowering(err) => err.kind().name(),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Error::Source(err) => err.kind().to_string(),
+            Error::Lexer(err) => err.kind().to_string(),
+            Error::Parser(err) => err.kind().to_string(),
+            Error::Lowering(err) => err.kind().to_string(),
+        }
+    }
+}
+
+/// Stable identifier of a kind of error, which users can search for or look up with
+/// `celo explain`.
+#[derive(Debug)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    /// Name of the kind of error, see [`Error::name`]
+    pub name: &'static str,
+    /// Long-form description in Markdown, with examples
+    pub explanation: &'static str,
 }
+
+/// Assigns codes to the variants of the error kinds and generates [`ERROR_CODES`] along with a
+/// `name` and `code` method for each kind, whose matches fail to compile if a variant has no code.
+macro_rules! error_codes {
+    ($($kind:ident { $($code:literal $name:ident,)* })*) => {
+        /// Codes of all kinds of errors. Codes are never reused, even if their error goes away.
+        pub static ERROR_CODES: &[ErrorCode] = &[$($(ErrorCode {
+            code: $code,
+            name: stringify!($name),
+            explanation: include_str!(concat!("explanations/", $code, ".md")),
+        },)*)*];
+
+        $(impl $kind {
+            /// Returns the name of the kind, which identifies it in machine-readable output.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($kind::$name { .. } => stringify!($name),)*
+                }
+            }
+
+            /// Returns the stable code of the kind, like `C0001`.
+            pub fn code(&self) -> &'static str {
+                match self {
+                    $($kind::$name { .. } => $code,)*
+                }
+            }
+        })*
+    };
+}
+
+// Parser errors start at C0001, lexer errors at C0101, lowering errors at C0201 and source errors
+// at C0301.
+error_codes! {
+    ParserErrorKind {
+        "C0001" UnmatchedBracket,
+        "C0002" UnclosedScope,
+        "C0003" UnexpectedToken,
+        "C0004" UnexpectedEof,
+        "C0005" UnknownMacro,
+        "C0006" NestingTooDeep,
+    }
+    LexerErrorKind {
+        "C0101" InvalidCharacter,
+        "C0102" InvalidEof,
+        "C0103" InvalidEscapeSequence,
+        "C0104" UnterminatedComment,
+        "C0105" InvalidDigit,
+        "C0106" MissingDigits,
+        "C0107" MissingExponent,
+        "C0108" InvalidNumberSuffix,
+        "C0109" InvalidUnicodeEscape,
+        "C0110" InvalidCharLiteral,
+        "C0111" InvalidMultilineString,
+        "C0112" InvalidInterpolation,
+    }
+    LoweringErrorKind {
+        "C0201" DuplicateFunction,
+        "C0202" DuplicateTest,
+        "C0203" InvalidInteger,
+        "C0204" MissingMain,
+        "C0205" UnknownFunction,
+        "C0206" UnknownVariable,
+        "C0207" UnexpandedMacro,
+        "C0208" AmbiguousFunction,
+    }
+    SourceErrorKind {
+        "C0301" FileNotFound,
+        "C0302" PermissionDenied,
+        "C0303" IoError,
+    }
+}
+
+/// Looks up an error code, ignoring case, or the name of a kind of error.
+pub fn find_error_code(query: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES
+        .iter()
+        .find(|code| code.code.eq_ignore_ascii_case(query) || code.name == query)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location() {
+            Some(location) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.path(),
+                location.line,
+                location.column,
+                self.message()
+            ),
+            None => write!(f, "{}: {}", self.path(), self.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,6 +1,12 @@
-use std::{fmt::Debug, rc::Rc};
+use std::{
+    fmt::{self, Debug},
+    rc::Rc,
+};
 
-use super::source::{Location, Source};
+use super::{
+    lexer::FormatPart,
+    source::{Location, Source},
+};
 
 /// Represents the entire HIR structure of a compile task.
 #[derive(Debug, Default)]
@@ -14,6 +20,10 @@ pub struct Module {
     pub source: Rc<Source>,
     pub submodules: Vec<usize>,
     pub functions: Vec<Box<Function>>,
+    /// Bodies of `test!` blocks, named like functions
+    pub tests: Vec<Box<Function>>,
+    /// Normalized paths of the sources imported with `import!`
+    pub imports: Vec<Rc<str>>,
 }
 
 impl Module {
@@ -22,6 +32,8 @@ impl Module {
             source,
             submodules: Vec::new(),
             functions: Vec::new(),
+            tests: Vec::new(),
+            imports: Vec::new(),
         }
     }
 }
@@ -31,6 +43,8 @@ pub struct Function {
     pub location: Location,
     pub name: Location,
     pub body: Scope,
+    /// Text of the `;;` doc comment before the function
+    pub doc: Option<String>,
     // todo
 }
 
@@ -40,6 +54,7 @@ impl Function {
             location,
             name,
             body,
+            doc: None,
         }
     }
 }
@@ -74,10 +89,15 @@ pub enum NodeKind {
     Integer,
     Float,
     String,
+    /// Character literal, which evaluates to its code point
+    Char,
+    FormatString(Box<FormatString>),
     Call,
     Variable,
     Assignment(Box<Assignment>),
     Group(Box<Group>),
+    If(Box<If>),
+    Try(Box<Try>),
     MacroIntermediate(Box<dyn MacroIntermediate>),
 }
 
@@ -93,6 +113,18 @@ impl Assignment {
     }
 }
 
+/// Interpolated string like `f"x = {.x}"`.
+#[derive(Debug)]
+pub struct FormatString {
+    pub parts: Vec<FormatPart>,
+}
+
+impl FormatString {
+    pub fn new(parts: Vec<FormatPart>) -> Self {
+        Self { parts }
+    }
+}
+
 #[derive(Debug)]
 pub struct Group {
     pub left_paren: Location,
@@ -110,5 +142,125 @@ impl Group {
     }
 }
 
+/// Conditional that pops an integer and runs the `then` scope if it is not zero.
+#[derive(Debug)]
+pub struct If {
+    pub if_keyword: Location,
+    pub then_scope: Scope,
+    pub else_keyword: Option<Location>,
+    pub else_scope: Option<Scope>,
+}
+
+impl If {
+    pub fn new(
+        if_keyword: Location,
+        then_scope: Scope,
+        else_keyword: Option<Location>,
+        else_scope: Option<Scope>,
+    ) -> Self {
+        Self {
+            if_keyword,
+            then_scope,
+            else_keyword,
+            else_scope,
+        }
+    }
+}
+
+/// Runs the `body` scope and, if it fails, the `handler` scope with the error on the stack.
+#[derive(Debug)]
+pub struct Try {
+    pub try_keyword: Location,
+    pub body: Scope,
+    pub catch_keyword: Location,
+    pub handler: Scope,
+}
+
+impl Try {
+    pub fn new(
+        try_keyword: Location,
+        body: Scope,
+        catch_keyword: Location,
+        handler: Scope,
+    ) -> Self {
+        Self {
+            try_keyword,
+            body,
+            catch_keyword,
+            handler,
+        }
+    }
+}
+
 // todo
 pub trait MacroIntermediate: Debug {}
+
+/// Readable dump of the HIR with the position and source text of every node.
+impl fmt::Display for Hir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for module in &self.modules {
+            writeln!(f, "module {}", module.source.path)?;
+            let functions = module.functions.iter().map(|function| ("fn", function));
+            let tests = module.tests.iter().map(|test| ("test", test));
+            for (keyword, function) in functions.chain(tests) {
+                let name = function.name;
+                writeln!(
+                    f,
+                    "  {keyword} {} {}:{}",
+                    &module.source[name], name.line, name.column
+                )?;
+                if let Some(doc) = &function.doc {
+                    writeln!(f, "    doc {doc:?}")?;
+                }
+                write_nodes(f, &module.source, &function.body.code, 2)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_nodes(
+    f: &mut fmt::Formatter<'_>,
+    source: &Source,
+    nodes: &[Node],
+    depth: usize,
+) -> fmt::Result {
+    for node in nodes {
+        let indent = "  ".repeat(depth);
+        let location = node.location;
+        write!(f, "{indent}{}:{} ", location.line, location.column)?;
+        let text = &source[location];
+        match &node.kind {
+            NodeKind::Integer => writeln!(f, "integer {text}")?,
+            NodeKind::Float => writeln!(f, "float {text}")?,
+            NodeKind::String => writeln!(f, "string {text}")?,
+            NodeKind::Char => writeln!(f, "char {text}")?,
+            NodeKind::FormatString(_) => writeln!(f, "format {text}")?,
+            NodeKind::Call => writeln!(f, "call {text}")?,
+            NodeKind::Variable => writeln!(f, "variable {text}")?,
+            NodeKind::Assignment(assignment) => {
+                writeln!(f, "assign {}", &source[assignment.variable])?
+            }
+            NodeKind::Group(group) => {
+                writeln!(f, "group")?;
+                write_nodes(f, source, &group.nodes, depth + 1)?;
+            }
+            NodeKind::If(if_node) => {
+                writeln!(f, "if")?;
+                write_nodes(f, source, &if_node.then_scope.code, depth + 1)?;
+                if let Some(else_scope) = &if_node.else_scope {
+                    writeln!(f, "{indent}else")?;
+                    write_nodes(f, source, &else_scope.code, depth + 1)?;
+                }
+            }
+            NodeKind::Try(try_node) => {
+                writeln!(f, "try")?;
+                write_nodes(f, source, &try_node.body.code, depth + 1)?;
+                writeln!(f, "{indent}catch")?;
+                write_nodes(f, source, &try_node.handler.code, depth + 1)?;
+            }
+            NodeKind::MacroIntermediate(intermediate) => writeln!(f, "macro {intermediate:?}")?,
+        }
+    }
+    Ok(())
+}
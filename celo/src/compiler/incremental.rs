@@ -0,0 +1,504 @@
+use std::{collections::HashMap, mem, ops::Range, rc::Rc};
+
+use super::{
+    error::Error,
+    experimental, hir,
+    lexer::Lexer,
+    parser::ParseHirStep,
+    source::{ColumnMode, Location, Source, Token, TokenKind},
+    Compiler,
+};
+
+/// Replacement of the bytes `start..end` of a source with `text`.
+#[derive(Clone, Debug)]
+pub struct TextEdit {
+    pub start: u32,
+    pub end: u32,
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionChange {
+    pub name: String,
+    /// Whether the change is to a `test!` block rather than a function
+    pub test: bool,
+    pub kind: ChangeKind,
+}
+
+/// What an edit caused a [`Document`] to do again.
+#[derive(Debug)]
+pub struct Update {
+    /// Byte range of the new source that was lexed again
+    pub relexed: Range<u32>,
+    /// Number of root macros that were parsed again
+    pub reparsed: usize,
+    /// Number of root macros that were kept
+    pub reused: usize,
+    pub changes: Vec<FunctionChange>,
+}
+
+/// Root macro invocation of a document with everything it defined.
+#[derive(Debug)]
+struct Item {
+    /// Where lexing of the item starts, right after the last token of the previous item
+    start: Location,
+    tokens: Vec<Token>,
+    /// Functions and tests of the item
+    module: hir::Module,
+    error: Option<Error>,
+}
+
+/// Source file open in an editor, which is lexed and parsed again only around edits.
+///
+/// The document is split into its root macros. After an edit, lexing restarts at the root macro
+/// before the edit and stops as soon as the tokens line up with a root macro after it again. Only
+/// the root macros in between are parsed again. Imports are not followed.
+#[derive(Debug)]
+pub struct Document {
+    source: Rc<Source>,
+    items: Vec<Item>,
+    /// Lexer error that ended the last item
+    lexer_error: Option<Error>,
+}
+
+impl Document {
+    pub fn new(compiler: &Compiler, source: Rc<Source>) -> Self {
+        let mut document = Self {
+            source: source.clone(),
+            items: Vec::new(),
+            lexer_error: None,
+        };
+        let (items, error) = lex_items(compiler, &source, file_start(), |_, _| false);
+        document.lexer_error = error;
+        document.items = items
+            .into_iter()
+            .map(|(start, tokens)| parse_item(compiler, &source, start, tokens))
+            .collect();
+        document
+    }
+
+    pub fn source(&self) -> &Rc<Source> {
+        &self.source
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = &hir::Function> {
+        self.items
+            .iter()
+            .flat_map(|item| &item.module.functions)
+            .map(|f| &**f)
+    }
+
+    pub fn tests(&self) -> impl Iterator<Item = &hir::Function> {
+        self.items
+            .iter()
+            .flat_map(|item| &item.module.tests)
+            .map(|f| &**f)
+    }
+
+    /// Returns the normalized paths of the sources the document imports.
+    pub fn imports(&self) -> impl Iterator<Item = &Rc<str>> {
+        self.items.iter().flat_map(|item| &item.module.imports)
+    }
+
+    /// Lends the functions and tests of the document to `f` as a single module.
+    ///
+    /// Panics if `f` adds or removes functions or tests.
+    pub fn with_module<R>(&mut self, f: impl FnOnce(&mut hir::Module) -> R) -> R {
+        let mut module = hir::Module::new(self.source.clone());
+        let mut counts = Vec::with_capacity(self.items.len());
+        for item in &mut self.items {
+            counts.push((item.module.functions.len(), item.module.tests.len()));
+            module.functions.append(&mut item.module.functions);
+            module.tests.append(&mut item.module.tests);
+        }
+        let lengths = (module.functions.len(), module.tests.len());
+        let result = f(&mut module);
+        assert_eq!(
+            (module.functions.len(), module.tests.len()),
+            lengths,
+            "functions of a document must be given back"
+        );
+        let mut functions = module.functions.into_iter();
+        let mut tests = module.tests.into_iter();
+        for (item, (function_count, test_count)) in self.items.iter_mut().zip(counts) {
+            item.module.functions = functions.by_ref().take(function_count).collect();
+            item.module.tests = tests.by_ref().take(test_count).collect();
+        }
+        result
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.items.iter().flat_map(|item| &item.tokens)
+    }
+
+    /// Returns the first error of the document.
+    pub fn error(&self) -> Option<&Error> {
+        self.items
+            .iter()
+            .find_map(|item| item.error.as_ref())
+            .or(self.lexer_error.as_ref())
+    }
+
+    /// Applies an edit and updates the tokens and functions around it.
+    ///
+    /// Panics if the range of the edit is out of bounds or splits a character.
+    pub fn edit(&mut self, compiler: &Compiler, edit: &TextEdit) -> Update {
+        let old_source = self.source.clone();
+        let old = &old_source.content;
+        let content = format!(
+            "{}{}{}",
+            &old[..edit.start as usize],
+            edit.text,
+            &old[edit.end as usize..]
+        );
+        let source = Source::from_string(old_source.path.clone(), content);
+        let edit_end = edit.start + edit.text.len() as u32;
+        let delta = i64::from(edit_end) - i64::from(edit.end);
+
+        // Documents with lexer errors are rare and small enough to start over
+        let mut first = match self.lexer_error {
+            Some(_) => 0,
+            None => self
+                .items
+                .iter()
+                .rposition(|item| item.start.start < edit.start)
+                .unwrap_or(0),
+        };
+        let relex_start = match first {
+            0 => file_start(),
+            _ => self.items[first].start,
+        };
+        let old_starts: HashMap<u32, usize> = self
+            .items
+            .iter()
+            .enumerate()
+            .skip(first + 1)
+            .filter(|_| self.lexer_error.is_none())
+            .map(|(index, item)| (item.start.start, index))
+            .collect();
+        // Index of the first old item that follows the relexed ones
+        let mut resync = self.items.len();
+        let (mut lexed, lexer_error) = lex_items(compiler, &source, relex_start, |start, _| {
+            if start.start < edit_end {
+                return false;
+            }
+            let old_start = (i64::from(start.start) - delta) as u32;
+            match old_starts.get(&old_start) {
+                Some(&index) => {
+                    resync = index;
+                    true
+                }
+                None => false,
+            }
+        });
+        let starts_with_macro = |tokens: &[Token]| {
+            tokens
+                .first()
+                .is_some_and(|token| token.kind == TokenKind::BangIdentifier)
+        };
+        if let Some((start, tokens)) = lexed.first_mut() {
+            if first > 0 && !starts_with_macro(tokens) {
+                // Tokens before the first root macro belong to the previous item
+                first -= 1;
+                let previous = &self.items[first];
+                *start = previous.start;
+                tokens.splice(0..0, previous.tokens.iter().copied());
+            }
+        }
+        let relexed_end = if resync < self.items.len() {
+            let last = lexed.last().and_then(|(_, tokens)| tokens.last());
+            last.map_or(relex_start.start, |token| token.location.end)
+        } else {
+            source.content.len() as u32
+        };
+
+        let shift = Shift::new(&old_source, &source, edit);
+        let mut replaced: Vec<Item> = self.items.drain(first..).collect();
+        let reused: Vec<Item> = replaced.drain(resync - first..).collect();
+        let new_items: Vec<Item> = lexed
+            .into_iter()
+            .map(|(start, tokens)| parse_item(compiler, &source, start, tokens))
+            .collect();
+        let changes = changes(&old_source, &replaced, &source, &new_items);
+        let update = Update {
+            relexed: relex_start.start..relexed_end,
+            reparsed: new_items.len(),
+            reused: first + reused.len(),
+            changes,
+        };
+        for item in &mut self.items {
+            item.module.source = source.clone();
+            if item.error.is_some() {
+                // Errors refer to the old source
+                let tokens = mem::take(&mut item.tokens);
+                *item = parse_item(compiler, &source, item.start, tokens);
+            }
+        }
+        self.items.extend(new_items);
+        for mut item in reused {
+            shift.item(&mut item);
+            item.module.source = source.clone();
+            if item.error.is_some() {
+                // Errors refer to the old source
+                item = parse_item(compiler, &source, item.start, item.tokens);
+            }
+            self.items.push(item);
+        }
+        self.source = source;
+        self.lexer_error = lexer_error;
+        update
+    }
+}
+
+/// Lexes from `start` and splits the tokens into items, which start at root macros outside of
+/// brackets. Stops before an item if `stop` returns true for its start and first token.
+fn lex_items(
+    compiler: &Compiler,
+    source: &Rc<Source>,
+    start: Location,
+    mut stop: impl FnMut(Location, Token) -> bool,
+) -> (Vec<(Location, Vec<Token>)>, Option<Error>) {
+    let mut lexer = Lexer::with_keywords(source.clone(), compiler.keywords().clone());
+    lexer.seek(start);
+    let mut items: Vec<(Location, Vec<Token>)> = Vec::new();
+    let mut item_start = start;
+    let mut depth = 0usize;
+    loop {
+        let token = match lexer.peek_token() {
+            Ok(Some(token)) => token,
+            Ok(None) => return (items, None),
+            Err(err) => return (items, Some(err)),
+        };
+        let root_macro = token.kind == TokenKind::BangIdentifier && depth == 0;
+        if root_macro && stop(item_start, token) {
+            return (items, None);
+        }
+        if root_macro || items.is_empty() {
+            items.push((item_start, Vec::new()));
+        }
+        match token.kind {
+            TokenKind::LeftParen | TokenKind::LeftSquare | TokenKind::LeftCurly => depth += 1,
+            TokenKind::RightParen | TokenKind::RightSquare | TokenKind::RightCurly => {
+                depth = depth.saturating_sub(1)
+            }
+            _ => {}
+        }
+        items.last_mut().expect("item was pushed").1.push(token);
+        item_start = end_of(source, token.location);
+        if let Err(err) = lexer.consume_token() {
+            return (items, Some(err));
+        }
+    }
+}
+
+fn parse_item(
+    compiler: &Compiler,
+    source: &Rc<Source>,
+    start: Location,
+    tokens: Vec<Token>,
+) -> Item {
+    let end = tokens
+        .last()
+        .map_or(start.start, |token| token.location.end);
+    let mut step = ParseHirStep::new(compiler, source.clone());
+    experimental::init(&mut step);
+    step.lexer.seek(start);
+    let (module, error) = match step.parse_partial_module(end) {
+        Ok(module) => (module, None),
+        Err(err) => (hir::Module::new(source.clone()), Some(err)),
+    };
+    Item {
+        start,
+        tokens,
+        module,
+        error,
+    }
+}
+
+/// Compares the functions of the replaced items with the ones that replaced them.
+fn changes(
+    old_source: &Source,
+    old_items: &[Item],
+    source: &Source,
+    new_items: &[Item],
+) -> Vec<FunctionChange> {
+    /// Whether a function is a test, and its name
+    type Key<'a> = (bool, &'a str);
+    /// Text and doc comment of a function
+    type Signature<'a> = (&'a str, Option<&'a str>);
+    fn signatures<'a>(source: &'a Source, items: &'a [Item]) -> Vec<(Key<'a>, Signature<'a>)> {
+        items
+            .iter()
+            .flat_map(|item| {
+                let functions = item.module.functions.iter().map(|f| (false, f));
+                functions.chain(item.module.tests.iter().map(|test| (true, test)))
+            })
+            .map(|(test, function)| {
+                let text = &source[function.location];
+                let name = &source[function.name];
+                ((test, name), (text, function.doc.as_deref()))
+            })
+            .collect()
+    }
+    let old = signatures(old_source, old_items);
+    let new = signatures(source, new_items);
+    let old_map: HashMap<_, _> = old.iter().copied().collect();
+    let new_map: HashMap<_, _> = new.iter().copied().collect();
+    let mut changes = Vec::new();
+    for (key, signature) in new {
+        let kind = match old_map.get(&key) {
+            None => ChangeKind::Added,
+            Some(&old) if old != signature => ChangeKind::Changed,
+            Some(_) => continue,
+        };
+        let (test, name) = key;
+        changes.push(FunctionChange {
+            name: name.into(),
+            test,
+            kind,
+        });
+    }
+    for ((test, name), _) in old {
+        if !new_map.contains_key(&(test, name)) {
+            changes.push(FunctionChange {
+                name: name.into(),
+                test,
+                kind: ChangeKind::Removed,
+            });
+        }
+    }
+    changes
+}
+
+/// Moves locations after an edit to where they are in the new source.
+struct Shift {
+    bytes: i64,
+    lines: i64,
+    /// Line of the end of the edit in the old source, where columns move too
+    line: u32,
+    columns: i64,
+}
+
+impl Shift {
+    fn new(old_source: &Source, source: &Source, edit: &TextEdit) -> Self {
+        let old_end = old_source.line_column(edit.end, ColumnMode::Chars);
+        let new_end = source.line_column(edit.start + edit.text.len() as u32, ColumnMode::Chars);
+        Self {
+            bytes: edit.text.len() as i64 - i64::from(edit.end - edit.start),
+            lines: i64::from(new_end.line) - i64::from(old_end.line),
+            line: old_end.line,
+            columns: i64::from(new_end.column) - i64::from(old_end.column),
+        }
+    }
+
+    fn location(&self, location: &mut Location) {
+        if location.line == self.line {
+            location.column = (i64::from(location.column) + self.columns) as u32;
+        }
+        location.line = (i64::from(location.line) + self.lines) as u32;
+        location.start = (i64::from(location.start) + self.bytes) as u32;
+        location.end = (i64::from(location.end) + self.bytes) as u32;
+    }
+
+    fn item(&self, item: &mut Item) {
+        self.location(&mut item.start);
+        for token in &mut item.tokens {
+            self.location(&mut token.location);
+        }
+        for function in item
+            .module
+            .functions
+            .iter_mut()
+            .chain(&mut item.module.tests)
+        {
+            self.location(&mut function.location);
+            self.location(&mut function.name);
+            self.scope(&mut function.body);
+        }
+    }
+
+    fn scope(&self, scope: &mut hir::Scope) {
+        self.location(&mut scope.start);
+        self.location(&mut scope.end);
+        self.nodes(&mut scope.code);
+    }
+
+    fn nodes(&self, nodes: &mut [hir::Node]) {
+        for node in nodes {
+            self.location(&mut node.location);
+            match &mut node.kind {
+                hir::NodeKind::FormatString(format_string) => {
+                    for part in &mut format_string.parts {
+                        self.location(&mut part.location);
+                    }
+                }
+                hir::NodeKind::Assignment(assignment) => {
+                    self.location(&mut assignment.arrow);
+                    self.location(&mut assignment.variable);
+                }
+                hir::NodeKind::Group(group) => {
+                    self.location(&mut group.left_paren);
+                    self.location(&mut group.right_paren);
+                    self.nodes(&mut group.nodes);
+                }
+                hir::NodeKind::If(if_node) => {
+                    self.location(&mut if_node.if_keyword);
+                    self.scope(&mut if_node.then_scope);
+                    if let Some(else_keyword) = &mut if_node.else_keyword {
+                        self.location(else_keyword);
+                    }
+                    if let Some(else_scope) = &mut if_node.else_scope {
+                        self.scope(else_scope);
+                    }
+                }
+                hir::NodeKind::Try(try_node) => {
+                    self.location(&mut try_node.try_keyword);
+                    self.scope(&mut try_node.body);
+                    self.location(&mut try_node.catch_keyword);
+                    self.scope(&mut try_node.handler);
+                }
+                hir::NodeKind::Integer
+                | hir::NodeKind::Float
+                | hir::NodeKind::String
+                | hir::NodeKind::Char
+                | hir::NodeKind::Call
+                | hir::NodeKind::Variable
+                | hir::NodeKind::MacroIntermediate(_) => {}
+            }
+        }
+    }
+}
+
+fn file_start() -> Location {
+    Location {
+        start: 0,
+        end: 0,
+        line: 1,
+        column: 1,
+    }
+}
+
+/// Returns the empty location right after a token.
+fn end_of(source: &Source, location: Location) -> Location {
+    let text = &source[location];
+    let (line, column) = match text.rfind('\n') {
+        Some(newline) => (
+            location.line + text.matches('\n').count() as u32,
+            text[newline + 1..].chars().count() as u32 + 1,
+        ),
+        None => (location.line, location.column + text.chars().count() as u32),
+    };
+    Location {
+        start: location.end,
+        end: location.end,
+        line,
+        column,
+    }
+}
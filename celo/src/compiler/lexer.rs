@@ -1,4 +1,8 @@
-use std::rc::Rc;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, mem,
+    rc::Rc,
+};
 
 use phf::{phf_map, Map};
 
@@ -7,10 +11,75 @@ use super::{
     source::{Location, Source, Token, TokenKind},
 };
 
+/// Keywords of the language, which are always reserved.
 pub const KEYWORDS: Map<&str, TokenKind> = phf_map! {
     "->" => TokenKind::RightArrow,
+    "if" => TokenKind::If,
+    "else" => TokenKind::Else,
+    "try" => TokenKind::Try,
+    "catch" => TokenKind::Catch,
 };
 
+/// Keywords of a compilation: the built-in [`KEYWORDS`] plus the ones registered at runtime, for
+/// example by a language edition or an embedded DSL.
+#[derive(Clone, Debug, Default)]
+pub struct KeywordTable {
+    custom: HashMap<Rc<str>, TokenKind>,
+}
+
+impl KeywordTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a word or operator to be lexed as `kind` instead of an identifier.
+    ///
+    /// Reserved words without a built-in meaning use [`TokenKind::Keyword`], others can alias a
+    /// built-in keyword. Returns `false` if the keyword is built-in, which cannot be overridden,
+    /// or if `kind` is not a keyword kind.
+    pub fn insert(&mut self, keyword: impl Into<Rc<str>>, kind: TokenKind) -> bool {
+        let is_keyword_kind = matches!(
+            kind,
+            TokenKind::If
+                | TokenKind::Else
+                | TokenKind::Try
+                | TokenKind::Catch
+                | TokenKind::RightArrow
+                | TokenKind::Keyword
+        );
+        let keyword = keyword.into();
+        if !is_keyword_kind || KEYWORDS.contains_key(&keyword) {
+            return false;
+        }
+        self.custom.insert(keyword, kind);
+        true
+    }
+
+    pub fn remove(&mut self, keyword: &str) {
+        self.custom.remove(keyword);
+    }
+
+    pub fn get(&self, word: &str) -> Option<TokenKind> {
+        if let Some(&kind) = KEYWORDS.get(word) {
+            return Some(kind);
+        }
+        if self.custom.is_empty() {
+            return None;
+        }
+        self.custom.get(word).copied()
+    }
+
+    /// Returns all keywords, the built-in ones first.
+    pub fn keywords(&self) -> impl Iterator<Item = &str> {
+        KEYWORDS
+            .keys()
+            .copied()
+            .chain(self.custom.keys().map(|keyword| &**keyword))
+    }
+}
+
+const INTEGER_SUFFIXES: [&str; 8] = ["i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64"];
+
 #[derive(Debug)]
 pub struct LexerError {
     source: Rc<Source>,
@@ -18,11 +87,74 @@ pub struct LexerError {
     kind: LexerErrorKind,
 }
 
-#[derive(Debug)]
+impl LexerError {
+    pub fn source(&self) -> &Rc<Source> {
+        &self.source
+    }
+
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    pub fn kind(&self) -> &LexerErrorKind {
+        &self.kind
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum LexerErrorKind {
     InvalidCharacter,
     InvalidEof,
     InvalidEscapeSequence,
+    /// `;(` block comment without a matching `);`
+    UnterminatedComment,
+    /// Digit that does not belong to the base of the number, as in `0b12`
+    InvalidDigit,
+    /// Base prefix without any digits, as in `0x`
+    MissingDigits,
+    /// Exponent without any digits, as in `1e+`
+    MissingExponent,
+    /// Unknown type suffix, or one that does not fit the literal, as in `1.5u8`
+    InvalidNumberSuffix,
+    /// `\u{...}` escape that is malformed or not a Unicode scalar value
+    InvalidUnicodeEscape,
+    /// Character literal that is empty or holds more than one character
+    InvalidCharLiteral,
+    /// `"""` string whose first line is not empty or whose lines are indented less than its
+    /// closing quotes
+    InvalidMultilineString,
+    /// Braces in a format string that do not hold a single `.variable`
+    InvalidInterpolation,
+}
+
+impl fmt::Display for LexerErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexerErrorKind::InvalidCharacter => write!(f, "invalid character"),
+            LexerErrorKind::InvalidEof => write!(f, "unexpected end of file"),
+            LexerErrorKind::InvalidEscapeSequence => write!(f, "invalid escape sequence"),
+            LexerErrorKind::UnterminatedComment => {
+                write!(f, "unterminated block comment, expected `);`")
+            }
+            LexerErrorKind::InvalidDigit => write!(f, "invalid digit for the base of the number"),
+            LexerErrorKind::MissingDigits => write!(f, "number has no digits"),
+            LexerErrorKind::MissingExponent => write!(f, "exponent has no digits"),
+            LexerErrorKind::InvalidNumberSuffix => write!(f, "invalid number suffix"),
+            LexerErrorKind::InvalidUnicodeEscape => write!(f, "invalid unicode escape"),
+            LexerErrorKind::InvalidCharLiteral => {
+                write!(f, "character literal must hold exactly one character")
+            }
+            LexerErrorKind::InvalidInterpolation => write!(
+                f,
+                "expected `.variable` between braces, write `{{{{` or `}}}}` for literal braces"
+            ),
+            LexerErrorKind::InvalidMultilineString => write!(
+                f,
+                "multi-line string must start with a line break and indent every line as far \
+                 as its closing quotes"
+            ),
+        }
+    }
 }
 
 pub struct Lexer {
@@ -33,7 +165,13 @@ pub struct Lexer {
     current: u32,
     current_line: u32,
     current_column: u32,
-    peek_buf: Option<Token>,
+    /// Tokens that were looked ahead at, with the doc comments before them
+    peek_buf: VecDeque<(Token, Vec<Location>)>,
+    /// Emit comments as tokens instead of skipping them
+    comments: bool,
+    /// `;;` doc comments directly before the last consumed token
+    doc_comments: Vec<Location>,
+    keywords: Rc<KeywordTable>,
 }
 
 impl Lexer {
@@ -46,7 +184,26 @@ impl Lexer {
             current: 0,
             current_line: 1,
             current_column: 1,
-            peek_buf: None,
+            peek_buf: VecDeque::new(),
+            comments: false,
+            doc_comments: Vec::new(),
+            keywords: Rc::default(),
+        }
+    }
+
+    /// Creates a lexer that recognizes the keywords of the table besides the built-in ones.
+    pub fn with_keywords(source: Rc<Source>, keywords: Rc<KeywordTable>) -> Self {
+        Self {
+            keywords,
+            ..Self::new(source)
+        }
+    }
+
+    /// Creates a lexer that emits `;` comments as [`TokenKind::Comment`] tokens.
+    pub fn with_comments(source: Rc<Source>) -> Self {
+        Self {
+            comments: true,
+            ..Self::new(source)
         }
     }
 
@@ -54,6 +211,41 @@ impl Lexer {
         self.source.clone()
     }
 
+    /// Continues lexing at the start of the location, which has to lie between tokens.
+    pub fn seek(&mut self, location: Location) {
+        self.current = location.start;
+        self.current_line = location.line;
+        self.current_column = location.column;
+        self.clear_location();
+        self.peek_buf.clear();
+        self.doc_comments.clear();
+    }
+
+    /// Returns the text of the `;;` doc comments before the last lexed token, which is the peeked
+    /// token if there is one.
+    pub fn doc_comment(&self) -> Option<String> {
+        let doc_comments = match self.peek_buf.front() {
+            Some((_, doc_comments)) => doc_comments,
+            None => &self.doc_comments,
+        };
+        if doc_comments.is_empty() {
+            return None;
+        }
+        let lines: Vec<&str> = doc_comments
+            .iter()
+            .map(|&location| {
+                let line = self.source[location].trim_start_matches(';');
+                line.strip_prefix(' ').unwrap_or(line).trim_end()
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Returns the source text after the current position.
+    fn rest(&self) -> &str {
+        &self.source.content[self.current as usize..]
+    }
+
     fn peek(&self) -> Option<char> {
         if self.current as usize >= self.source.content.len() {
             return None;
@@ -61,7 +253,7 @@ impl Lexer {
         self.source.content[self.current as usize..].chars().next()
     }
 
-    fn next(&mut self) {
+    fn bump(&mut self) {
         let Some(c) = self.peek() else {
             return;
         };
@@ -107,13 +299,34 @@ impl Lexer {
 
     fn skip_comment(&mut self) {
         while let Some(c) = self.peek() {
-            self.next();
             if c == '\n' {
                 break;
             }
+            self.bump();
         }
     }
 
+    /// Skips a `;( ... );` comment after its `;`, including nested block comments.
+    fn skip_block_comment(&mut self) -> Result<()> {
+        self.bump();
+        let mut depth = 1;
+        while depth > 0 {
+            if self.rest().starts_with(";(") {
+                depth += 1;
+            } else if self.rest().starts_with(");") {
+                depth -= 1;
+            } else if self.peek().is_none() {
+                return Err(self.make_error(LexerErrorKind::UnterminatedComment));
+            } else {
+                self.bump();
+                continue;
+            }
+            self.bump();
+            self.bump();
+        }
+        Ok(())
+    }
+
     fn parse_minus_prefix(&mut self) -> Result<Token> {
         let Some(c) = self.peek() else {
             return self.parse_identifier(false);
@@ -129,23 +342,23 @@ impl Lexer {
         let mut bang = false;
         while let Some(c) = self.peek() {
             if c == '.' {
-                self.next();
+                self.bump();
                 return Err(self.make_error(LexerErrorKind::InvalidCharacter));
             }
             if bang {
                 if c == '!' || is_identifier(c, false) {
-                    self.next();
+                    self.bump();
                     return Err(self.make_error(LexerErrorKind::InvalidCharacter));
                 }
                 break;
             }
             if is_identifier(c, first) {
-                self.next();
+                self.bump();
                 first = false;
                 continue;
             }
             if c == '!' {
-                self.next();
+                self.bump();
                 if dot {
                     return Err(self.make_error(LexerErrorKind::InvalidCharacter));
                 }
@@ -163,72 +376,317 @@ impl Lexer {
         let mut token = self.make_token(TokenKind::Identifier);
         let string =
             &self.source.content[token.location.start as usize..token.location.end as usize];
-        if let Some(&kind) = KEYWORDS.get(string) {
+        if let Some(kind) = self.keywords.get(string) {
             token.kind = kind;
         }
         Ok(token)
     }
 
+    /// Lexes a number after its first character, which is a digit or a minus sign.
     fn parse_number(&mut self) -> Result<Token> {
-        let mut dot = false;
-        while let Some(c) = self.peek() {
-            if c.is_ascii_digit() {
-                self.next();
-                continue;
+        let first = self.source.content[self.start as usize..].chars().next();
+        let zero = match first {
+            Some('-') if self.peek() == Some('0') => {
+                self.bump();
+                true
             }
-            if c == '.' {
-                self.next();
-                if dot {
+            Some('-') => false,
+            first => first == Some('0'),
+        };
+        let radix = match self.peek() {
+            Some('x') if zero => 16,
+            Some('o') if zero => 8,
+            Some('b') if zero => 2,
+            _ => 10,
+        };
+        let mut float = false;
+        if radix != 10 {
+            self.bump();
+            if self.parse_digits(radix)? == 0 {
+                return Err(self.make_error(LexerErrorKind::MissingDigits));
+            }
+            if self.peek() == Some('.') {
+                self.bump();
+                return Err(self.make_error(LexerErrorKind::InvalidDigit));
+            }
+        } else {
+            self.parse_digits(10)?;
+            if self.peek() == Some('.') {
+                self.bump();
+                self.parse_digits(10)?;
+                if self.peek() == Some('.') {
+                    self.bump();
                     return Err(self.make_error(LexerErrorKind::InvalidCharacter));
                 }
-                dot = true;
+                float = true;
+            }
+            if let Some('e' | 'E') = self.peek() {
+                self.bump();
+                if let Some('+' | '-') = self.peek() {
+                    self.bump();
+                }
+                if self.parse_digits(10)? == 0 {
+                    return Err(self.make_error(LexerErrorKind::MissingExponent));
+                }
+                float = true;
+            }
+        }
+        let suffix_start = self.current;
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_alphanumeric() && c != '_' {
+                break;
+            }
+            self.bump();
+        }
+        let suffix = &self.source.content[suffix_start as usize..self.current as usize];
+        match suffix {
+            "" if float => Ok(self.make_token(TokenKind::Float)),
+            "" => Ok(self.make_token(TokenKind::Integer)),
+            "f32" | "f64" if radix == 10 => Ok(self.make_token(TokenKind::Float)),
+            _ if !float && INTEGER_SUFFIXES.contains(&suffix) => {
+                Ok(self.make_token(TokenKind::Integer))
+            }
+            _ => Err(self.make_error(LexerErrorKind::InvalidNumberSuffix)),
+        }
+    }
+
+    /// Consumes digits of the radix and `_` separators, returning the number of digits.
+    fn parse_digits(&mut self, radix: u32) -> Result<usize> {
+        let mut digits = 0;
+        while let Some(c) = self.peek() {
+            if c == '_' {
+                self.bump();
                 continue;
             }
+            if c.is_digit(radix) {
+                self.bump();
+                digits += 1;
+                continue;
+            }
+            if c.is_ascii_digit() {
+                self.bump();
+                return Err(self.make_error(LexerErrorKind::InvalidDigit));
+            }
             break;
         }
-        if dot {
-            return Ok(self.make_token(TokenKind::Float));
-        }
-        Ok(self.make_token(TokenKind::Integer))
+        Ok(digits)
     }
 
+    /// Lexes a string after its opening quote.
     fn parse_string(&mut self) -> Result<Token> {
+        if self.rest().starts_with("\"\"") {
+            self.bump();
+            self.bump();
+            return self.parse_multiline_string();
+        }
         loop {
             let Some(c) = self.peek() else {
                 return Err(self.make_error(LexerErrorKind::InvalidEof));
             };
-            self.next();
-            if c == '"' {
-                break;
+            self.bump();
+            match c {
+                '"' => break,
+                '\\' => self.parse_escape()?,
+                _ => (),
             }
-            if c != '\\' {
-                continue;
+        }
+        Ok(self.make_token(TokenKind::String))
+    }
+
+    /// Lexes a `"""` string after its opening quotes, see [`strip_indentation`].
+    fn parse_multiline_string(&mut self) -> Result<Token> {
+        let content_start = self.current as usize;
+        loop {
+            if self.rest().starts_with("\"\"\"") {
+                break;
             }
-            // Escape sequences
-            let Some(escape) = self.peek() else {
+            let Some(c) = self.peek() else {
                 return Err(self.make_error(LexerErrorKind::InvalidEof));
             };
-            self.next();
-            match escape {
-                '"' | '\\' | 'n' | 'r' | 't' => (),
-                _ => return Err(self.make_error(LexerErrorKind::InvalidEscapeSequence)),
+            self.bump();
+            if c == '\\' {
+                self.parse_escape()?;
             }
         }
+        let content = &self.source.content[content_start..self.current as usize];
+        let valid = strip_indentation(content).is_some();
+        for _ in 0..3 {
+            self.bump();
+        }
+        if !valid {
+            return Err(self.make_error(LexerErrorKind::InvalidMultilineString));
+        }
         Ok(self.make_token(TokenKind::String))
     }
 
+    /// Lexes a raw string after its `r`, which ends at a quote followed by as many `#` as it
+    /// started with. Returns `None` if no string follows.
+    fn parse_raw_string(&mut self) -> Result<Option<Token>> {
+        let hashes = self.rest().len() - self.rest().trim_start_matches('#').len();
+        if !self.rest()[hashes..].starts_with('"') {
+            return Ok(None);
+        }
+        for _ in 0..=hashes {
+            self.bump();
+        }
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let Some(length) = self.rest().find(&terminator) else {
+            while self.peek().is_some() {
+                self.bump();
+            }
+            return Err(self.make_error(LexerErrorKind::InvalidEof));
+        };
+        let end = self.current as usize + length + terminator.len();
+        while (self.current as usize) < end {
+            self.bump();
+        }
+        Ok(Some(self.make_token(TokenKind::String)))
+    }
+
+    /// Lexes a format string after its opening quote, see [`split_format_string`].
+    fn parse_format_string(&mut self) -> Result<Token> {
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.make_error(LexerErrorKind::InvalidEof));
+            };
+            self.bump();
+            match c {
+                '"' => break,
+                '\\' => self.parse_escape()?,
+                '{' if self.peek() == Some('{') => self.bump(),
+                '}' if self.peek() == Some('}') => self.bump(),
+                '{' => {
+                    let dot = self.peek() == Some('.');
+                    self.bump();
+                    let mut length = 0;
+                    while dot && self.peek().is_some_and(|c| is_identifier(c, length == 0)) {
+                        self.bump();
+                        length += 1;
+                    }
+                    if length == 0 || self.peek() != Some('}') {
+                        self.bump();
+                        return Err(self.make_error(LexerErrorKind::InvalidInterpolation));
+                    }
+                    self.bump();
+                }
+                '}' => return Err(self.make_error(LexerErrorKind::InvalidInterpolation)),
+                _ => (),
+            }
+        }
+        Ok(self.make_token(TokenKind::FormatString))
+    }
+
+    /// Lexes a character literal after its opening quote.
+    fn parse_char(&mut self) -> Result<Token> {
+        match self.peek() {
+            None => return Err(self.make_error(LexerErrorKind::InvalidEof)),
+            Some('\'' | '\n') => {
+                self.bump();
+                return Err(self.make_error(LexerErrorKind::InvalidCharLiteral));
+            }
+            Some('\\') => {
+                self.bump();
+                self.parse_escape()?;
+            }
+            Some(_) => self.bump(),
+        }
+        match self.peek() {
+            None => Err(self.make_error(LexerErrorKind::InvalidEof)),
+            Some('\'') => {
+                self.bump();
+                Ok(self.make_token(TokenKind::Char))
+            }
+            Some(_) => {
+                self.bump();
+                Err(self.make_error(LexerErrorKind::InvalidCharLiteral))
+            }
+        }
+    }
+
+    /// Checks an escape sequence after its backslash.
+    fn parse_escape(&mut self) -> Result<()> {
+        let Some(escape) = self.peek() else {
+            return Err(self.make_error(LexerErrorKind::InvalidEof));
+        };
+        self.bump();
+        match escape {
+            '"' | '\'' | '\\' | 'n' | 'r' | 't' | '0' => Ok(()),
+            'x' => {
+                for _ in 0..2 {
+                    if !self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                        return Err(self.make_error(LexerErrorKind::InvalidEscapeSequence));
+                    }
+                    self.bump();
+                }
+                Ok(())
+            }
+            'u' => {
+                let digits = self
+                    .rest()
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.split_once('}'))
+                    .map(|(digits, _)| digits)
+                    .filter(|digits| {
+                        (1..=6).contains(&digits.len())
+                            && digits.chars().all(|c| c.is_ascii_hexdigit())
+                    });
+                let Some(digits) = digits else {
+                    return Err(self.make_error(LexerErrorKind::InvalidUnicodeEscape));
+                };
+                let valid = u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .is_some();
+                for _ in 0..digits.len() + 2 {
+                    self.bump();
+                }
+                if !valid {
+                    return Err(self.make_error(LexerErrorKind::InvalidUnicodeEscape));
+                }
+                Ok(())
+            }
+            _ => Err(self.make_error(LexerErrorKind::InvalidEscapeSequence)),
+        }
+    }
+
     fn parse_token(&mut self) -> Result<Option<Token>> {
+        self.doc_comments.clear();
+        // Line breaks since the last comment, a blank line separates doc comments from the token
+        let mut line_breaks = 0;
         loop {
             let Some(c) = self.peek() else {
                 break Ok(None);
             };
             self.clear_location();
-            self.next();
+            self.bump();
             if c.is_ascii_whitespace() {
+                if c == '\n' {
+                    line_breaks += 1;
+                    if line_breaks > 1 {
+                        self.doc_comments.clear();
+                    }
+                }
                 continue;
             }
             match c {
-                ';' => self.skip_comment(),
+                ';' => {
+                    let doc = self.peek() == Some(';');
+                    if self.peek() == Some('(') {
+                        self.skip_block_comment()?;
+                    } else {
+                        self.skip_comment();
+                    }
+                    if self.comments {
+                        break Ok(Some(self.make_token(TokenKind::Comment)));
+                    }
+                    if doc {
+                        let location = self.make_location();
+                        self.doc_comments.push(location);
+                    } else {
+                        self.doc_comments.clear();
+                    }
+                    line_breaks = 0;
+                }
                 '(' => break Ok(Some(self.make_token(TokenKind::LeftParen))),
                 ')' => break Ok(Some(self.make_token(TokenKind::RightParen))),
                 '[' => break Ok(Some(self.make_token(TokenKind::LeftSquare))),
@@ -238,6 +696,17 @@ impl Lexer {
                 '-' => break self.parse_minus_prefix().map(Some),
                 '.' => break self.parse_identifier(true).map(Some),
                 '"' => break self.parse_string().map(Some),
+                '\'' => break self.parse_char().map(Some),
+                'f' if self.peek() == Some('"') => {
+                    self.bump();
+                    break self.parse_format_string().map(Some);
+                }
+                'r' => {
+                    if let Some(token) = self.parse_raw_string()? {
+                        break Ok(Some(token));
+                    }
+                    break self.parse_identifier(false).map(Some);
+                }
                 '0'..='9' => break self.parse_number().map(Some),
                 _ => {
                     if is_identifier(c, true) {
@@ -250,19 +719,77 @@ impl Lexer {
     }
 
     pub fn peek_token(&mut self) -> Result<Option<Token>> {
-        if self.peek_buf.is_some() {
-            return Ok(self.peek_buf);
+        self.peek_nth(0)
+    }
+
+    /// Returns the token `n` tokens after the next one without consuming anything.
+    pub fn peek_nth(&mut self, n: usize) -> Result<Option<Token>> {
+        while self.peek_buf.len() <= n {
+            let Some(token) = self.parse_token()? else {
+                return Ok(None);
+            };
+            let doc_comments = mem::take(&mut self.doc_comments);
+            self.peek_buf.push_back((token, doc_comments));
         }
-        self.peek_buf = self.parse_token()?;
-        Ok(self.peek_buf)
+        Ok(Some(self.peek_buf[n].0))
     }
 
     pub fn consume_token(&mut self) -> Result<()> {
-        if self.peek_buf.take().is_none() {
-            self.parse_token()?;
+        match self.peek_buf.pop_front() {
+            Some((_, doc_comments)) => self.doc_comments = doc_comments,
+            None => _ = self.parse_token()?,
         }
         Ok(())
     }
+
+    /// Saves the position of the lexer, to [rewind](Self::rewind) to it later.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            current: self.current,
+            line: self.current_line,
+            column: self.current_column,
+            peek_buf: self.peek_buf.clone(),
+            doc_comments: self.doc_comments.clone(),
+        }
+    }
+
+    /// Returns to a saved position, so that the tokens after it are lexed again.
+    ///
+    /// Only the lexer is reset: anything a macro handler added to the HIR in the meantime stays.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.current = checkpoint.current;
+        self.current_line = checkpoint.line;
+        self.current_column = checkpoint.column;
+        self.clear_location();
+        self.peek_buf = checkpoint.peek_buf;
+        self.doc_comments = checkpoint.doc_comments;
+    }
+}
+
+/// Yields the remaining tokens. Lexing goes on after an error, right behind the faulty text.
+impl Iterator for Lexer {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.peek_token() {
+            Ok(Some(token)) => {
+                self.peek_buf.pop_front();
+                Some(Ok(token))
+            }
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Position of a [Lexer] that it can [rewind](Lexer::rewind) to.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    current: u32,
+    line: u32,
+    column: u32,
+    peek_buf: VecDeque<(Token, Vec<Location>)>,
+    doc_comments: Vec<Location>,
 }
 
 fn is_identifier(c: char, first: bool) -> bool {
@@ -277,3 +804,227 @@ fn is_identifier(c: char, first: bool) -> bool {
     }
     false
 }
+
+/// Returns the value of a string literal including its quotes.
+///
+/// Raw strings are taken verbatim and `"""` strings have their indentation stripped. `\xNN`
+/// escapes stand for the character U+00NN, so that every byte value can be embedded.
+pub fn unescape(literal: &str) -> String {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return raw[hashes + 1..raw.len() - hashes - 1].to_string();
+    }
+    if let Some(content) = literal.strip_prefix("\"\"\"") {
+        let content = &content[..content.len() - 3];
+        let content = strip_indentation(content).expect("lexer validated multi-line string");
+        return unescape_text(&content);
+    }
+    unescape_text(&literal[1..literal.len() - 1])
+}
+
+/// Returns the value of a character literal including its quotes.
+pub fn unescape_char(literal: &str) -> char {
+    let text = unescape_text(&literal[1..literal.len() - 1]);
+    text.chars().next().expect("lexer validated character")
+}
+
+/// Resolves the escape sequences of text inside a string literal.
+pub fn unescape_text(text: &str) -> String {
+    let mut string = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('r') => string.push('\r'),
+            Some('t') => string.push('\t'),
+            Some('0') => string.push('\0'),
+            Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                string.extend(
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32),
+                );
+            }
+            Some('u') => {
+                let digits: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                string.extend(
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32),
+                );
+            }
+            Some(c) => string.push(c),
+            None => (),
+        }
+    }
+    string
+}
+
+/// Strips the indentation of the content of a `"""` string.
+///
+/// The content has to start with a line break and end with the indentation of the closing
+/// quotes on their own line, which is removed from every line. Returns `None` if a line that is
+/// not blank is indented less.
+pub fn strip_indentation(content: &str) -> Option<String> {
+    let (first, rest) = content.split_once('\n')?;
+    if !first.trim().is_empty() {
+        return None;
+    }
+    let (body, indentation) = rest.rsplit_once('\n').unwrap_or(("", rest));
+    if !indentation.trim().is_empty() {
+        return None;
+    }
+    let mut lines = Vec::new();
+    for line in body.split('\n') {
+        match line.strip_prefix(indentation) {
+            Some(line) => lines.push(line),
+            None if line.trim().is_empty() => lines.push(""),
+            None => return None,
+        }
+    }
+    Some(lines.join("\n"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatPartKind {
+    /// Text with escape sequences
+    Text,
+    /// `.variable` to convert to a string
+    Variable,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FormatPart {
+    pub location: Location,
+    pub kind: FormatPartKind,
+}
+
+/// Splits a format string token like `f"x = {.x}"` into text and variables.
+///
+/// Doubled braces stand for a single brace, the text part after them starts at the second one.
+pub fn split_format_string(source: &Source, location: Location) -> Vec<FormatPart> {
+    let literal = &source[location];
+    let mut parts = Vec::new();
+    let mut part = FormatPart {
+        location: Location {
+            start: location.start + 2,
+            end: location.start + 2,
+            line: location.line,
+            column: location.column + 2,
+        },
+        kind: FormatPartKind::Text,
+    };
+    // Position of the next character
+    let mut position = part.location;
+    let mut chars = literal[2..literal.len() - 1].chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut consumed = vec![c];
+        match c {
+            '{' | '}' => {
+                let closing = part.kind == FormatPartKind::Variable;
+                part.location.end = position.start;
+                if part.location.start < part.location.end {
+                    parts.push(part);
+                }
+                let next = chars.peek().copied();
+                let kind = match (c, next) {
+                    ('{', Some('.')) => FormatPartKind::Variable,
+                    _ => FormatPartKind::Text,
+                };
+                part = FormatPart {
+                    location: advance(position, c),
+                    kind,
+                };
+                if !closing && next == Some(c) {
+                    consumed.extend(chars.next());
+                }
+            }
+            // Keep escape sequences whole, `\u{...}` contains braces
+            '\\' => match chars.next() {
+                Some('u') => {
+                    consumed.push('u');
+                    for c in chars.by_ref() {
+                        consumed.push(c);
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+                c => consumed.extend(c),
+            },
+            _ => (),
+        }
+        position = consumed.into_iter().fold(position, advance);
+    }
+    part.location.end = position.start;
+    if part.location.start < part.location.end {
+        parts.push(part);
+    }
+    parts
+}
+
+/// Moves an empty location past a character.
+fn advance(location: Location, c: char) -> Location {
+    let start = location.start + c.len_utf8() as u32;
+    let (line, column) = match c {
+        '\n' => (location.line + 1, 1),
+        _ => (location.line, location.column + 1),
+    };
+    Location {
+        start,
+        end: start,
+        line,
+        column,
+    }
+}
+
+/// Returns the value of an integer literal, or `None` if it does not fit its type.
+///
+/// Integers without a suffix are `i64`, like all integers at runtime. `u64` literals keep their
+/// bits, so values above `i64::MAX` wrap around to negative integers.
+pub fn parse_integer(literal: &str) -> Option<i64> {
+    let (negative, literal) = match literal.strip_prefix('-') {
+        Some(literal) => (true, literal),
+        None => (false, literal),
+    };
+    let (radix, literal) = match literal.get(..2) {
+        Some("0x") => (16, &literal[2..]),
+        Some("0o") => (8, &literal[2..]),
+        Some("0b") => (2, &literal[2..]),
+        _ => (10, literal),
+    };
+    let (digits, suffix) = literal.split_at(literal.find(['i', 'u']).unwrap_or(literal.len()));
+    let digits = digits.replace('_', "");
+    let magnitude = i128::from_str_radix(&digits, radix).ok()?;
+    let value = if negative { -magnitude } else { magnitude };
+    let fits = match suffix {
+        "i8" => i8::try_from(value).is_ok(),
+        "i16" => i16::try_from(value).is_ok(),
+        "i32" => i32::try_from(value).is_ok(),
+        "u8" => u8::try_from(value).is_ok(),
+        "u16" => u16::try_from(value).is_ok(),
+        "u32" => u32::try_from(value).is_ok(),
+        "u64" => return u64::try_from(value).ok().map(|value| value as i64),
+        _ => true,
+    };
+    if !fits {
+        return None;
+    }
+    i64::try_from(value).ok()
+}
+
+/// Returns the value of a float literal, rounded to `f32` precision for the `f32` suffix.
+pub fn parse_float(literal: &str) -> f64 {
+    let literal = literal.replace('_', "");
+    if let Some(literal) = literal.strip_suffix("f32") {
+        let value: f32 = literal.parse().expect("lexer validated float");
+        return value.into();
+    }
+    let literal = literal.strip_suffix("f64").unwrap_or(&literal);
+    literal.parse().expect("lexer validated float")
+}
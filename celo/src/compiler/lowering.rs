@@ -0,0 +1,550 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, mem,
+    rc::Rc,
+};
+
+use maquina::vm::intrinsic::Intrinsic;
+use phf::{phf_map, Map};
+
+use super::{
+    error::{Error, Result},
+    hir,
+    lexer::{parse_float, parse_integer, unescape, unescape_char, unescape_text, FormatPartKind},
+    mir,
+    source::{Location, Source},
+};
+
+pub const INTRINSICS: Map<&str, Intrinsic> = phf_map! {
+    "dup" => Intrinsic::Dup,
+    "drop" => Intrinsic::Drop,
+    "swap" => Intrinsic::Swap,
+    "over" => Intrinsic::Over,
+    "rot" => Intrinsic::Rot,
+    "+" => Intrinsic::Add,
+    "-" => Intrinsic::Sub,
+    "*" => Intrinsic::Mul,
+    "/" => Intrinsic::Div,
+    "%" => Intrinsic::Rem,
+    "=" => Intrinsic::Eq,
+    "<" => Intrinsic::Lt,
+    "<=" => Intrinsic::Le,
+    ">" => Intrinsic::Gt,
+    ">=" => Intrinsic::Ge,
+    "not" => Intrinsic::Not,
+    "to-string" => Intrinsic::ToString,
+    "concat" => Intrinsic::Concat,
+    "print" => Intrinsic::Print,
+    "array" => Intrinsic::Array,
+    "map" => Intrinsic::Map,
+    "push" => Intrinsic::Push,
+    "pop" => Intrinsic::Pop,
+    "get" => Intrinsic::Get,
+    "set" => Intrinsic::Set,
+    "has" => Intrinsic::Has,
+    "remove" => Intrinsic::Remove,
+    "len" => Intrinsic::Len,
+    "throw" => Intrinsic::Throw,
+    "read-file" => Intrinsic::ReadFile,
+    "write-file" => Intrinsic::WriteFile,
+    "assert" => Intrinsic::Assert,
+};
+
+#[derive(Debug)]
+pub struct LoweringError {
+    source: Rc<Source>,
+    location: Option<Location>,
+    kind: LoweringErrorKind,
+}
+
+#[derive(Debug)]
+pub enum LoweringErrorKind {
+    DuplicateFunction {
+        previous: Location,
+    },
+    DuplicateTest {
+        previous: Location,
+    },
+    InvalidInteger,
+    MissingMain,
+    UnknownFunction,
+    UnknownVariable,
+    UnexpandedMacro,
+    /// Call of a function that more than one imported module defines
+    AmbiguousFunction,
+}
+
+impl LoweringError {
+    pub fn source(&self) -> &Rc<Source> {
+        &self.source
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+
+    pub fn kind(&self) -> &LoweringErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for LoweringErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoweringErrorKind::DuplicateFunction { previous } => write!(
+                f,
+                "function is already defined at {}:{}",
+                previous.line, previous.column
+            ),
+            LoweringErrorKind::DuplicateTest { previous } => write!(
+                f,
+                "test is already defined at {}:{}",
+                previous.line, previous.column
+            ),
+            LoweringErrorKind::InvalidInteger => write!(f, "integer literal is out of range"),
+            LoweringErrorKind::MissingMain => write!(f, "missing `main` function"),
+            LoweringErrorKind::UnknownFunction => write!(f, "unknown function"),
+            LoweringErrorKind::UnknownVariable => write!(f, "unknown variable"),
+            LoweringErrorKind::UnexpandedMacro => {
+                write!(f, "macro was not expanded before lowering")
+            }
+            LoweringErrorKind::AmbiguousFunction => {
+                write!(f, "function is defined by more than one imported module")
+            }
+        }
+    }
+}
+
+/// Functions and globals of earlier compilations that new code can refer to.
+///
+/// This allows compiling REPL entries into an already running program.
+#[derive(Clone, Debug, Default)]
+pub struct Environment {
+    /// Number of functions in the program, new functions are numbered after them
+    pub function_count: usize,
+    pub functions: HashMap<Rc<str>, usize>,
+    pub globals: HashMap<Rc<str>, u32>,
+}
+
+pub struct LowerMirStep<'a> {
+    hir: &'a hir::Hir,
+    mir: mir::Mir,
+    environment: Environment,
+    /// Function indices by name for each module
+    function_tables: Vec<HashMap<&'a str, usize>>,
+    /// Function indices by name across all modules
+    imported_functions: HashMap<&'a str, usize>,
+    /// Names that more than one module defines, which modules can only call if they define them
+    ambiguous_functions: HashSet<&'a str>,
+    /// Whether `test!` blocks are lowered
+    lower_tests: bool,
+}
+
+impl<'a> LowerMirStep<'a> {
+    pub fn new(hir: &'a hir::Hir) -> Self {
+        Self::with_environment(hir, Environment::default())
+    }
+
+    pub fn with_environment(hir: &'a hir::Hir, environment: Environment) -> Self {
+        Self {
+            hir,
+            mir: mir::Mir {
+                first_function: environment.function_count,
+                ..Default::default()
+            },
+            environment,
+            function_tables: Vec::new(),
+            imported_functions: HashMap::new(),
+            ambiguous_functions: HashSet::new(),
+            lower_tests: false,
+        }
+    }
+
+    /// Also lowers the `test!` blocks of all modules into [`mir::Mir::tests`].
+    pub fn with_tests(mut self) -> Self {
+        self.lower_tests = true;
+        self
+    }
+
+    pub fn run(mut self) -> Result<mir::Mir> {
+        self.lower_modules()?;
+        let Some(main_module) = self.hir.modules.first() else {
+            return Ok(self.mir);
+        };
+        let Some(&entry) = self.function_tables[0].get("main") else {
+            return Err(make_error(
+                &main_module.source,
+                None,
+                LoweringErrorKind::MissingMain,
+            ));
+        };
+        self.mir.entry = Some(entry);
+        Ok(self.mir)
+    }
+
+    /// Lowers the HIR without requiring a `main` function.
+    ///
+    /// Returns the environment extended by the new functions.
+    pub fn run_incremental(mut self) -> Result<(mir::Mir, Environment)> {
+        self.lower_modules()?;
+        for (&name, &index) in &self.imported_functions {
+            if !self.ambiguous_functions.contains(name) {
+                self.environment.functions.insert(name.into(), index);
+            }
+        }
+        self.environment.function_count += self.mir.functions.len();
+        Ok((self.mir, self.environment))
+    }
+
+    fn lower_modules(&mut self) -> Result<()> {
+        for (module_index, module) in self.hir.modules.iter().enumerate() {
+            self.declare_module(module_index, module)?;
+        }
+        if self.lower_tests {
+            for (module_index, module) in self.hir.modules.iter().enumerate() {
+                self.declare_tests(module_index, module)?;
+            }
+        }
+        for table in &self.function_tables {
+            for (&name, &index) in table {
+                if self.imported_functions.insert(name, index).is_some() {
+                    self.ambiguous_functions.insert(name);
+                }
+            }
+        }
+        for (module_index, module) in self.hir.modules.iter().enumerate() {
+            for function in &module.functions {
+                let body = self.lower_function(module_index, function)?;
+                let index = self.function_tables[module_index][&module.source[function.name]];
+                self.mir.functions[index - self.environment.function_count].body = body;
+            }
+        }
+        if self.lower_tests {
+            let hir_tests = self
+                .hir
+                .modules
+                .iter()
+                .enumerate()
+                .flat_map(|(index, module)| module.tests.iter().map(move |test| (index, test)));
+            for (test, (module_index, hir_test)) in hir_tests.enumerate() {
+                let body = self.lower_function(module_index, hir_test)?;
+                let index = self.mir.tests[test].function;
+                self.mir.functions[index - self.environment.function_count].body = body;
+            }
+        }
+        Ok(())
+    }
+
+    fn declare_tests(&mut self, module_index: usize, module: &'a hir::Module) -> Result<()> {
+        let mut names: HashMap<&str, Location> = HashMap::new();
+        for test in &module.tests {
+            let name = &module.source[test.name];
+            if let Some(&previous) = names.get(name) {
+                return Err(make_error(
+                    &module.source,
+                    Some(test.name),
+                    LoweringErrorKind::DuplicateTest { previous },
+                ));
+            }
+            names.insert(name, test.name);
+            let index = self.environment.function_count + self.mir.functions.len();
+            self.mir.functions.push(Box::new(mir::Function {
+                module: module_index,
+                location: test.location,
+                name: test.name,
+                body: mir::Code::default(),
+            }));
+            self.mir.tests.push(mir::Test {
+                name: name.into(),
+                function: index,
+            });
+        }
+        Ok(())
+    }
+
+    fn declare_module(&mut self, module_index: usize, module: &'a hir::Module) -> Result<()> {
+        let mut table: HashMap<&str, usize> = HashMap::new();
+        let mut functions = Vec::new();
+        for function in &module.functions {
+            let name = &module.source[function.name];
+            if let Some(&previous) = table.get(name) {
+                let previous = self.mir.functions[previous - self.environment.function_count].name;
+                return Err(make_error(
+                    &module.source,
+                    Some(function.name),
+                    LoweringErrorKind::DuplicateFunction { previous },
+                ));
+            }
+            let index = self.environment.function_count + self.mir.functions.len();
+            self.mir.functions.push(Box::new(mir::Function {
+                module: module_index,
+                location: function.location,
+                name: function.name,
+                body: mir::Code::default(),
+            }));
+            table.insert(name, index);
+            functions.push(index);
+        }
+        self.mir.modules.push(Box::new(mir::Module {
+            source: module.source.clone(),
+            submodules: module.submodules.clone(),
+            functions,
+        }));
+        self.function_tables.push(table);
+        Ok(())
+    }
+
+    fn lower_function(&self, module_index: usize, function: &hir::Function) -> Result<mir::Code> {
+        let mut lowering = FunctionLowering {
+            source: &self.hir.modules[module_index].source,
+            functions: &self.function_tables[module_index],
+            imported_functions: &self.imported_functions,
+            ambiguous_functions: &self.ambiguous_functions,
+            known_functions: &self.environment.functions,
+            globals: None,
+            variables: HashMap::new(),
+            code: mir::Code::default(),
+        };
+        lowering.lower_nodes(&function.body.code, true)?;
+        Ok(lowering.code)
+    }
+}
+
+/// Lowers top-level code, such as a REPL entry, whose variables are globals of the environment.
+pub fn lower_code(
+    source: &Rc<Source>,
+    nodes: &[hir::Node],
+    environment: &mut Environment,
+) -> Result<mir::Code> {
+    let mut lowering = FunctionLowering {
+        source,
+        functions: &HashMap::new(),
+        imported_functions: &HashMap::new(),
+        ambiguous_functions: &HashSet::new(),
+        known_functions: &environment.functions,
+        globals: Some(&mut environment.globals),
+        variables: HashMap::new(),
+        code: mir::Code::default(),
+    };
+    lowering.lower_nodes(nodes, true)?;
+    Ok(lowering.code)
+}
+
+struct FunctionLowering<'a> {
+    source: &'a Rc<Source>,
+    functions: &'a HashMap<&'a str, usize>,
+    /// Functions of all modules
+    imported_functions: &'a HashMap<&'a str, usize>,
+    ambiguous_functions: &'a HashSet<&'a str>,
+    /// Functions of earlier compilations
+    known_functions: &'a HashMap<Rc<str>, usize>,
+    /// Set if variables are stored in globals instead of locals
+    globals: Option<&'a mut HashMap<Rc<str>, u32>>,
+    variables: HashMap<&'a str, u32>,
+    code: mir::Code,
+}
+
+impl<'a> FunctionLowering<'a> {
+    fn emit(&mut self, location: Location, kind: mir::InstructionKind) {
+        self.code
+            .instructions
+            .push(mir::Instruction::new(location, kind));
+    }
+
+    fn patch_jump(&mut self, jump: usize) {
+        let target = self.code.instructions.len();
+        match &mut self.code.instructions[jump].kind {
+            mir::InstructionKind::Jump(old)
+            | mir::InstructionKind::JumpIfZero(old)
+            | mir::InstructionKind::Try(old) => *old = target,
+            _ => unreachable!("patching non-jump instruction"),
+        }
+    }
+
+    /// Lowers a sequence of nodes. If `tail` is set, the last node is in tail position.
+    fn lower_nodes(&mut self, nodes: &[hir::Node], tail: bool) -> Result<()> {
+        for (i, node) in nodes.iter().enumerate() {
+            self.lower_node(node, tail && i == nodes.len() - 1)?;
+        }
+        Ok(())
+    }
+
+    fn lower_node(&mut self, node: &hir::Node, tail: bool) -> Result<()> {
+        let source = self.source;
+        let text = &source[node.location];
+        let kind = match &node.kind {
+            hir::NodeKind::Integer => match parse_integer(text) {
+                Some(integer) => mir::InstructionKind::Integer(integer),
+                None => {
+                    return Err(make_error(
+                        source,
+                        Some(node.location),
+                        LoweringErrorKind::InvalidInteger,
+                    ))
+                }
+            },
+            hir::NodeKind::Float => mir::InstructionKind::Float(parse_float(text)),
+            hir::NodeKind::String => mir::InstructionKind::String(unescape(text).into()),
+            hir::NodeKind::Char => {
+                mir::InstructionKind::Integer(u32::from(unescape_char(text)).into())
+            }
+            hir::NodeKind::FormatString(format_string) => {
+                return self.lower_format_string(node.location, format_string)
+            }
+            hir::NodeKind::Call => {
+                if !self.functions.contains_key(text) && self.ambiguous_functions.contains(text) {
+                    return Err(make_error(
+                        source,
+                        Some(node.location),
+                        LoweringErrorKind::AmbiguousFunction,
+                    ));
+                }
+                let function = self
+                    .functions
+                    .get(text)
+                    .or_else(|| self.imported_functions.get(text))
+                    .or_else(|| self.known_functions.get(text));
+                if let Some(&function) = function {
+                    mir::InstructionKind::Call { function, tail }
+                } else if let Some(&intrinsic) = INTRINSICS.get(text) {
+                    mir::InstructionKind::Intrinsic(intrinsic)
+                } else {
+                    return Err(make_error(
+                        source,
+                        Some(node.location),
+                        LoweringErrorKind::UnknownFunction,
+                    ));
+                }
+            }
+            hir::NodeKind::Variable => return self.lower_load(node.location),
+            hir::NodeKind::Assignment(assignment) => {
+                let name = &source[assignment.variable][1..];
+                if let Some(globals) = &mut self.globals {
+                    let next = globals.len() as u32;
+                    let global = *globals.entry(name.into()).or_insert(next);
+                    self.emit(node.location, mir::InstructionKind::StoreGlobal(global));
+                    return Ok(());
+                }
+                let next = self.variables.len() as u32;
+                let local = *self.variables.entry(name).or_insert(next);
+                self.code.locals = self.code.locals.max(local + 1);
+                mir::InstructionKind::Store(local)
+            }
+            hir::NodeKind::Group(group) => return self.lower_nodes(&group.nodes, tail),
+            hir::NodeKind::If(if_node) => return self.lower_if(if_node, tail),
+            hir::NodeKind::Try(try_node) => return self.lower_try(try_node, tail),
+            hir::NodeKind::MacroIntermediate(_) => {
+                return Err(make_error(
+                    source,
+                    Some(node.location),
+                    LoweringErrorKind::UnexpandedMacro,
+                ))
+            }
+        };
+        self.emit(node.location, kind);
+        Ok(())
+    }
+
+    /// Loads the `.variable` at the location.
+    fn lower_load(&mut self, location: Location) -> Result<()> {
+        let name = &self.source[location][1..];
+        let kind = if let Some(globals) = &self.globals {
+            globals
+                .get(name)
+                .map(|&global| mir::InstructionKind::LoadGlobal(global))
+        } else {
+            self.variables
+                .get(name)
+                .map(|&local| mir::InstructionKind::Load(local))
+        };
+        let Some(kind) = kind else {
+            return Err(make_error(
+                self.source,
+                Some(location),
+                LoweringErrorKind::UnknownVariable,
+            ));
+        };
+        self.emit(location, kind);
+        Ok(())
+    }
+
+    /// Lowers a format string into pushing its pieces and concatenating them.
+    fn lower_format_string(
+        &mut self,
+        location: Location,
+        format_string: &hir::FormatString,
+    ) -> Result<()> {
+        let mut pieces = 0;
+        let mut text = String::new();
+        let mut text_location = None;
+        for part in &format_string.parts {
+            if part.kind == FormatPartKind::Text {
+                text.push_str(&unescape_text(&self.source[part.location]));
+                text_location.get_or_insert(part.location);
+                continue;
+            }
+            if let Some(location) = text_location.take() {
+                let text = mem::take(&mut text);
+                self.emit(location, mir::InstructionKind::String(text.into()));
+                self.concat_piece(location, &mut pieces);
+            }
+            self.lower_load(part.location)?;
+            let to_string = mir::InstructionKind::Intrinsic(Intrinsic::ToString);
+            self.emit(part.location, to_string);
+            self.concat_piece(part.location, &mut pieces);
+        }
+        if text_location.is_some() || pieces == 0 {
+            let location = text_location.unwrap_or(location);
+            self.emit(location, mir::InstructionKind::String(text.into()));
+            self.concat_piece(location, &mut pieces);
+        }
+        Ok(())
+    }
+
+    /// Concatenates a piece of a format string with the ones before it.
+    fn concat_piece(&mut self, location: Location, pieces: &mut usize) {
+        *pieces += 1;
+        if *pieces > 1 {
+            self.emit(location, mir::InstructionKind::Intrinsic(Intrinsic::Concat));
+        }
+    }
+
+    fn lower_if(&mut self, if_node: &hir::If, tail: bool) -> Result<()> {
+        let jump_else = self.code.instructions.len();
+        self.emit(if_node.if_keyword, mir::InstructionKind::JumpIfZero(0));
+        self.lower_nodes(&if_node.then_scope.code, tail)?;
+        let (Some(else_keyword), Some(else_scope)) = (if_node.else_keyword, &if_node.else_scope)
+        else {
+            self.patch_jump(jump_else);
+            return Ok(());
+        };
+        let jump_end = self.code.instructions.len();
+        self.emit(else_keyword, mir::InstructionKind::Jump(0));
+        self.patch_jump(jump_else);
+        self.lower_nodes(&else_scope.code, tail)?;
+        self.patch_jump(jump_end);
+        Ok(())
+    }
+
+    fn lower_try(&mut self, try_node: &hir::Try, tail: bool) -> Result<()> {
+        let try_instruction = self.code.instructions.len();
+        self.emit(try_node.try_keyword, mir::InstructionKind::Try(0));
+        // Calls in the body must keep the frame of the handler alive
+        self.lower_nodes(&try_node.body.code, false)?;
+        self.emit(try_node.body.end, mir::InstructionKind::EndTry);
+        let jump_end = self.code.instructions.len();
+        self.emit(try_node.catch_keyword, mir::InstructionKind::Jump(0));
+        self.patch_jump(try_instruction);
+        self.lower_nodes(&try_node.handler.code, tail)?;
+        self.patch_jump(jump_end);
+        Ok(())
+    }
+}
+
+fn make_error(source: &Rc<Source>, location: Option<Location>, kind: LoweringErrorKind) -> Error {
+    Error::Lowering(Box::new(LoweringError {
+        source: source.clone(),
+        location,
+        kind,
+    }))
+}
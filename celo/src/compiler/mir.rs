@@ -1,11 +1,25 @@
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
+
+use maquina::vm::intrinsic::Intrinsic;
 
 use super::source::{Location, Source};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Mir {
     pub modules: Vec<Box<Module>>,
     pub functions: Vec<Box<Function>>,
+    /// The `main` function of the main module
+    pub entry: Option<usize>,
+    /// Program index of the first function. Function indices in the MIR are program indices.
+    pub first_function: usize,
+    /// Tests, if lowered, whose functions follow the functions of all modules
+    pub tests: Vec<Test>,
+}
+
+#[derive(Debug)]
+pub struct Test {
+    pub name: Rc<str>,
+    pub function: usize,
 }
 
 #[derive(Debug)]
@@ -17,10 +31,81 @@ pub struct Module {
 
 #[derive(Debug)]
 pub struct Function {
+    pub module: usize,
     pub location: Location,
     pub name: Location,
     pub body: Code,
 }
 
+#[derive(Debug, Default)]
+pub struct Code {
+    pub locals: u32,
+    pub instructions: Vec<Instruction>,
+}
+
+#[derive(Debug)]
+pub struct Instruction {
+    pub location: Location,
+    pub kind: InstructionKind,
+}
+
+impl Instruction {
+    pub fn new(location: Location, kind: InstructionKind) -> Self {
+        Self { location, kind }
+    }
+}
+
 #[derive(Debug)]
-pub struct Code {} // todo - design code
+pub enum InstructionKind {
+    Integer(i64),
+    Float(f64),
+    String(Rc<str>),
+    Load(u32),
+    Store(u32),
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+    /// Call of a function by its program index, marked as `tail` if nothing follows it before the function returns.
+    Call {
+        function: usize,
+        tail: bool,
+    },
+    Intrinsic(Intrinsic),
+    /// Jump to an instruction index.
+    Jump(usize),
+    /// Pops an integer and jumps to an instruction index if it is zero.
+    JumpIfZero(usize),
+    /// Installs an error handler starting at an instruction index.
+    Try(usize),
+    /// Removes the innermost error handler.
+    EndTry,
+}
+
+/// Readable dump of the MIR with one line per instruction.
+impl fmt::Display for Mir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (offset, function) in self.functions.iter().enumerate() {
+            let source = &self.modules[function.module].source;
+            let name = function.name;
+            writeln!(
+                f,
+                "fn {} {} ({}:{}:{}) locals {}",
+                self.first_function + offset,
+                &source[name],
+                source.path,
+                name.line,
+                name.column,
+                function.body.locals
+            )?;
+            for (index, instruction) in function.body.instructions.iter().enumerate() {
+                writeln!(f, "  {index:>4} {:?}", instruction.kind)?;
+            }
+        }
+        if let Some(entry) = self.entry {
+            writeln!(f, "entry {entry}")?;
+        }
+        for test in &self.tests {
+            writeln!(f, "test {} {}", test.name, test.function)?;
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,14 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    path::{Component, Path, PathBuf},
     rc::Rc,
 };
 
 use super::{
     error::{Error, Result},
     hir,
-    lexer::Lexer,
+    lexer::{split_format_string, Lexer},
     source::{Location, Source, Token, TokenKind},
     Compiler,
 };
@@ -34,8 +36,64 @@ pub enum ParserErrorKind {
         got: Option<TokenKind>,
     },
     UnknownMacro,
+    NestingTooDeep,
 }
 
+impl ParserError {
+    pub fn source(&self) -> &Rc<Source> {
+        &self.source
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+
+    pub fn kind(&self) -> &ParserErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for ParserErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParserErrorKind::UnclosedScope => write!(f, "unclosed scope"),
+            ParserErrorKind::UnexpectedToken {
+                expected: Some(expected),
+                got,
+            } => write!(f, "expected {expected}, got {got}"),
+            ParserErrorKind::UnexpectedToken {
+                expected: None,
+                got,
+            } => write!(f, "unexpected {got}"),
+            ParserErrorKind::UnexpectedEof {
+                expected: Some(expected),
+            } => write!(f, "expected {expected}, got end of file"),
+            ParserErrorKind::UnexpectedEof { expected: None } => {
+                write!(f, "unexpected end of file")
+            }
+            ParserErrorKind::UnmatchedBracket {
+                expected,
+                got: Some(got),
+                ..
+            } => write!(f, "unmatched bracket: expected {expected}, got {got}"),
+            ParserErrorKind::UnmatchedBracket {
+                expected,
+                got: None,
+                ..
+            } => {
+                write!(f, "unmatched bracket: expected {expected}, got end of file")
+            }
+            ParserErrorKind::UnknownMacro => write!(f, "unknown macro"),
+            ParserErrorKind::NestingTooDeep => {
+                write!(f, "brackets are nested deeper than {MAX_NESTING} levels")
+            }
+        }
+    }
+}
+
+/// Deepest nesting of brackets the recursive parser accepts before giving up.
+pub const MAX_NESTING: usize = 256;
+
 pub type MacroHandler = fn(&mut ParseHirStep) -> Result<()>;
 
 pub struct ParseHirStep<'a> {
@@ -46,7 +104,13 @@ pub struct ParseHirStep<'a> {
     hir: hir::Hir,
     current_module: usize,
     /// Queue of modules that are yet to be parsed
-    source_queue: VecDeque<usize>,
+    source_queue: VecDeque<Rc<Source>>,
+    /// Paths of all sources that were parsed or queued
+    known_paths: HashSet<Rc<str>>,
+    /// Doc comment before the root macro being expanded
+    doc_comment: Option<String>,
+    /// Number of brackets currently open
+    depth: usize,
     // todo
 }
 
@@ -55,19 +119,44 @@ impl<'a> ParseHirStep<'a> {
     pub fn new(compiler: &'a Compiler, main_source: Rc<Source>) -> Self {
         Self {
             compiler,
-            lexer: Lexer::new(main_source),
+            lexer: Lexer::with_keywords(main_source, compiler.keywords().clone()),
             root_scope: MacroScope::default(),
             macro_scopes: Vec::new(),
             hir: hir::Hir::default(),
             current_module: 0,
             source_queue: VecDeque::new(),
+            known_paths: HashSet::new(),
+            doc_comment: None,
+            depth: 0,
         }
     }
 
     pub fn run(mut self) -> Result<hir::Hir> {
+        let path = normalize(Path::new(&*self.lexer.source().path));
+        self.known_paths.insert(path.to_string_lossy().into());
         _ = self.parse_module(false)?;
-        while let Some(module_index) = self.source_queue.pop_front() {
-            self.current_module = module_index;
+        while let Some(source) = self.source_queue.pop_front() {
+            self.lexer = Lexer::with_keywords(source, self.compiler.keywords().clone());
+            self.parse_module(false)?;
+        }
+        Ok(self.hir)
+    }
+
+    /// Parses the given imports of the main source and everything they import, but not the main
+    /// source itself, which is left as an empty module 0.
+    ///
+    /// This completes a module that is kept up to date separately, see
+    /// [`Document`](super::incremental::Document).
+    pub fn run_imports(mut self, imports: impl IntoIterator<Item = Rc<str>>) -> Result<hir::Hir> {
+        let source = self.lexer.source();
+        let path = normalize(Path::new(&*source.path));
+        self.known_paths.insert(path.to_string_lossy().into());
+        self.hir.modules.push(Box::new(hir::Module::new(source)));
+        for path in imports {
+            self.queue(path)?;
+        }
+        while let Some(source) = self.source_queue.pop_front() {
+            self.lexer = Lexer::with_keywords(source, self.compiler.keywords().clone());
             self.parse_module(false)?;
         }
         Ok(self.hir)
@@ -87,6 +176,11 @@ impl<'a> ParseHirStep<'a> {
             .add(name.into(), handler);
     }
 
+    /// Returns the names of all root-level macros.
+    pub fn root_macros(&self) -> impl Iterator<Item = &str> {
+        self.root_scope.names()
+    }
+
     pub fn resolve_macro(&self, name: &str) -> Option<MacroHandler> {
         // root-level macros cannot be overridden
         if let Some(handler) = self.root_scope.resolve(name) {
@@ -100,21 +194,50 @@ impl<'a> ParseHirStep<'a> {
         None
     }
 
+    /// Queues a source file to be parsed as a module, unless it is already known.
+    ///
+    /// Relative paths are resolved against the directory of the current source.
+    pub fn import(&mut self, path: &str) -> Result<()> {
+        let current = self.lexer.source();
+        let directory = Path::new(&*current.path).parent().unwrap_or(Path::new(""));
+        let path: Rc<str> = normalize(&directory.join(path)).to_string_lossy().into();
+        self.hir.modules[self.current_module]
+            .imports
+            .push(path.clone());
+        self.queue(path)
+    }
+
+    fn queue(&mut self, path: Rc<str>) -> Result<()> {
+        if !self.known_paths.insert(path.clone()) {
+            return Ok(());
+        }
+        let source = Source::load_with(self.compiler.file_provider(), path)?;
+        self.source_queue.push_back(source);
+        Ok(())
+    }
+
+    /// Takes the doc comment written before the root macro being expanded.
+    pub fn take_doc_comment(&mut self) -> Option<String> {
+        self.doc_comment.take()
+    }
+
     pub fn add_function(&mut self, function: hir::Function) {
         self.hir.modules[self.current_module]
             .functions
             .push(Box::new(function));
     }
+
+    pub fn add_test(&mut self, test: hir::Function) {
+        self.hir.modules[self.current_module]
+            .tests
+            .push(Box::new(test));
+    }
 }
 
 /// Macro facing functions for parsing
 impl<'a> ParseHirStep<'a> {
     pub fn make_error(&mut self, location: Option<Location>, kind: ParserErrorKind) -> Error {
-        Error::Parser(Box::new(ParserError {
-            source: self.lexer.source(),
-            location,
-            kind,
-        }))
+        make_error(&self.lexer.source(), location, kind)
     }
 
     pub fn expect_token(&mut self, expected: TokenKind) -> Result<Token> {
@@ -176,26 +299,69 @@ impl<'a> ParseHirStep<'a> {
             .modules
             .push(Box::new(hir::Module::new(self.lexer.source())));
         self.macro_scopes.push(MacroScope::default());
-        let source = self.lexer.source();
-        loop {
-            let Some(token) = self.lexer.peek_token()? else {
-                break;
-            };
+        while let Some(token) = self.lexer.peek_token()? {
             if is_submodule && token.kind != TokenKind::BangIdentifier {
                 break;
             }
-            let macro_token = self.expect_token(TokenKind::BangIdentifier)?.location;
-            let macro_name = &source[macro_token].trim_end_matches('!');
-            let Some(macro_handler) = self.resolve_macro(macro_name) else {
-                return Err(self.make_error(Some(macro_token), ParserErrorKind::UnknownMacro));
-            };
-            (macro_handler)(self)?;
+            self.parse_root_macro()?;
         }
         self.current_module = previous_module_index;
         self.macro_scopes.pop().unwrap();
         Ok(module_index)
     }
 
+    /// Parses the root macros of the current source that start before the byte offset `end`,
+    /// continuing from the current position of the lexer. Imports are not followed.
+    ///
+    /// This parses a part of a module again after an edit, see
+    /// [`Document`](super::incremental::Document).
+    pub fn parse_partial_module(mut self, end: u32) -> Result<hir::Module> {
+        self.hir
+            .modules
+            .push(Box::new(hir::Module::new(self.lexer.source())));
+        self.current_module = 0;
+        self.macro_scopes.push(MacroScope::default());
+        loop {
+            match self.lexer.peek_token() {
+                Ok(Some(token)) if token.location.start < end => self.parse_root_macro()?,
+                // Errors past the end belong to whatever follows
+                Err(err) if err.location().is_some_and(|l| l.start < end) => return Err(err),
+                _ => break,
+            }
+        }
+        Ok(*self.hir.modules.swap_remove(0))
+    }
+
+    /// Expands the root macro at the current position.
+    pub fn parse_root_macro(&mut self) -> Result<()> {
+        self.doc_comment = self.lexer.doc_comment();
+        let macro_token = self.expect_token(TokenKind::BangIdentifier)?.location;
+        let source = self.lexer.source();
+        let macro_name = &source[macro_token].trim_end_matches('!');
+        let Some(macro_handler) = self.resolve_macro(macro_name) else {
+            return Err(self.make_error(Some(macro_token), ParserErrorKind::UnknownMacro));
+        };
+        (macro_handler)(self)
+    }
+
+    /// Parses nodes until the end of the source, as in top-level code.
+    pub fn parse_code(&mut self) -> Result<Vec<hir::Node>> {
+        let mut nodes = Vec::new();
+        while let Some(node) = self.parse_node()? {
+            nodes.push(node);
+        }
+        if let Some(token) = self.lexer.peek_token()? {
+            return Err(self.make_error(
+                Some(token.location),
+                ParserErrorKind::UnexpectedToken {
+                    expected: None,
+                    got: token.kind,
+                },
+            ));
+        }
+        Ok(nodes)
+    }
+
     /// Parses a group of nodes surrounded by curly braces.
     pub fn parse_scope(&mut self) -> Result<hir::Scope> {
         let left_curly = self.expect_token(TokenKind::LeftCurly)?.location;
@@ -227,7 +393,24 @@ impl<'a> ParseHirStep<'a> {
         let Some(token) = self.lexer.peek_token()? else {
             return Ok(None);
         };
+        if matches!(
+            token.kind,
+            TokenKind::RightParen | TokenKind::RightSquare | TokenKind::RightCurly
+        ) {
+            return Ok(None);
+        }
         let mut node = hir::Node::new(token.location, hir::NodeKind::Integer); // No kind set yet
+        if self.depth >= MAX_NESTING {
+            return Err(self.make_error(Some(token.location), ParserErrorKind::NestingTooDeep));
+        }
+        self.depth += 1;
+        let result = self.parse_node_kind(token, &mut node);
+        self.depth -= 1;
+        result?;
+        Ok(Some(node))
+    }
+
+    fn parse_node_kind(&mut self, token: Token, node: &mut hir::Node) -> Result<()> {
         match token.kind {
             TokenKind::Integer => {
                 self.lexer.consume_token()?;
@@ -241,18 +424,25 @@ impl<'a> ParseHirStep<'a> {
                 self.lexer.consume_token()?;
                 node.kind = hir::NodeKind::String;
             }
+            TokenKind::FormatString => {
+                self.lexer.consume_token()?;
+                let parts = split_format_string(&self.lexer.source(), token.location);
+                node.kind = hir::NodeKind::FormatString(Box::new(hir::FormatString::new(parts)));
+            }
+            TokenKind::Char => {
+                self.lexer.consume_token()?;
+                node.kind = hir::NodeKind::Char;
+            }
             TokenKind::LeftParen => {
                 let group = self.parse_group()?;
-                node = hir::Node::new(
+                *node = hir::Node::new(
                     group.left_paren.span_to(group.right_paren),
                     hir::NodeKind::Group(Box::new(group)),
                 );
             }
-            TokenKind::RightParen => return Ok(None),
-            TokenKind::LeftSquare => unimplemented!("square-brackets?"),
-            TokenKind::RightSquare => return Ok(None),
-            TokenKind::LeftCurly => unimplemented!("scopes?"),
-            TokenKind::RightCurly => return Ok(None),
+            TokenKind::RightParen | TokenKind::RightSquare | TokenKind::RightCurly => {
+                unreachable!("closing brackets end the code")
+            }
             TokenKind::Identifier => {
                 self.lexer.consume_token()?;
                 node.kind = hir::NodeKind::Call;
@@ -261,10 +451,27 @@ impl<'a> ParseHirStep<'a> {
                 self.lexer.consume_token()?;
                 node.kind = hir::NodeKind::Variable;
             }
-            TokenKind::BangIdentifier => unimplemented!("macros"),
-            TokenKind::RightArrow => node = self.parse_assignment()?,
+            TokenKind::RightArrow => *node = self.parse_assignment()?,
+            TokenKind::If => *node = self.parse_if()?,
+            TokenKind::Try => *node = self.parse_try()?,
+            // Macros are only expanded at the root and `[ ]` and `{ }` have no meaning in code yet
+            TokenKind::BangIdentifier
+            | TokenKind::LeftSquare
+            | TokenKind::LeftCurly
+            | TokenKind::Else
+            | TokenKind::Catch
+            | TokenKind::Keyword
+            | TokenKind::Comment => {
+                return Err(self.make_error(
+                    Some(token.location),
+                    ParserErrorKind::UnexpectedToken {
+                        expected: None,
+                        got: token.kind,
+                    },
+                ))
+            }
         }
-        Ok(Some(node))
+        Ok(())
     }
 
     fn parse_assignment(&mut self) -> Result<hir::Node> {
@@ -275,6 +482,48 @@ impl<'a> ParseHirStep<'a> {
             kind: hir::NodeKind::Assignment(Box::new(hir::Assignment { arrow, variable })),
         })
     }
+
+    fn parse_if(&mut self) -> Result<hir::Node> {
+        let if_keyword = self.expect_token(TokenKind::If)?.location;
+        let then_scope = self.parse_scope()?;
+        let mut end = then_scope.end;
+        let mut else_keyword = None;
+        let mut else_scope = None;
+        if let Some(token) = self.lexer.peek_token()? {
+            if token.kind == TokenKind::Else {
+                self.lexer.consume_token()?;
+                let scope = self.parse_scope()?;
+                end = scope.end;
+                else_keyword = Some(token.location);
+                else_scope = Some(scope);
+            }
+        }
+        Ok(hir::Node::new(
+            if_keyword.span_to(end),
+            hir::NodeKind::If(Box::new(hir::If::new(
+                if_keyword,
+                then_scope,
+                else_keyword,
+                else_scope,
+            ))),
+        ))
+    }
+
+    fn parse_try(&mut self) -> Result<hir::Node> {
+        let try_keyword = self.expect_token(TokenKind::Try)?.location;
+        let body = self.parse_scope()?;
+        let catch_keyword = self.expect_token(TokenKind::Catch)?.location;
+        let handler = self.parse_scope()?;
+        Ok(hir::Node::new(
+            try_keyword.span_to(handler.end),
+            hir::NodeKind::Try(Box::new(hir::Try::new(
+                try_keyword,
+                body,
+                catch_keyword,
+                handler,
+            ))),
+        ))
+    }
 }
 
 #[derive(Default)]
@@ -290,4 +539,41 @@ impl MacroScope {
     pub fn resolve(&self, name: &str) -> Option<MacroHandler> {
         self.handlers.get(name).copied()
     }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+}
+
+pub(crate) fn make_error(
+    source: &Rc<Source>,
+    location: Option<Location>,
+    kind: ParserErrorKind,
+) -> Error {
+    Error::Parser(Box::new(ParserError {
+        source: source.clone(),
+        location,
+        kind,
+    }))
+}
+
+/// Removes `.` components and `..` components that follow a directory, so that different
+/// spellings of a path name the same module.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
 }
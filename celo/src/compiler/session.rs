@@ -0,0 +1,85 @@
+use std::rc::Rc;
+
+use maquina::vm::program::Program;
+
+use super::{
+    codegen,
+    error::{Error, Result},
+    experimental,
+    lexer::{Lexer, LexerErrorKind},
+    lowering::{self, Environment, LowerMirStep},
+    parser::ParseHirStep,
+    source::{Source, TokenKind},
+    Compiler,
+};
+
+/// Incrementally compiles entries of a REPL into a program that keeps running on the same VM.
+#[derive(Default)]
+pub struct Session {
+    environment: Environment,
+}
+
+impl Session {
+    /// Compiles an entry into the program.
+    ///
+    /// Entries starting with a macro like `fn!` add definitions. Other entries are top-level
+    /// code, whose variables persist between entries, compiled into a new function whose index
+    /// is returned.
+    pub fn compile(&mut self, source: Rc<Source>, program: &mut Program) -> Result<Option<u32>> {
+        let compiler = Compiler::new(source.clone());
+        let mut hir_step = ParseHirStep::new(&compiler, source.clone());
+        experimental::init(&mut hir_step);
+        let Some(token) = hir_step.lexer.peek_token()? else {
+            return Ok(None);
+        };
+        let mut environment = self.environment.clone();
+        environment.function_count = program.functions.len();
+        let function = if token.kind == TokenKind::BangIdentifier {
+            let hir = hir_step.run()?;
+            let (mir, extended) =
+                LowerMirStep::with_environment(&hir, environment).run_incremental()?;
+            program.functions.extend(codegen::generate_functions(&mir));
+            environment = extended;
+            None
+        } else {
+            let nodes = hir_step.parse_code()?;
+            let code = lowering::lower_code(&source, &nodes, &mut environment)?;
+            let location = nodes.first().map(|node| node.location).unwrap_or_default();
+            program
+                .functions
+                .push(codegen::generate_code("<repl>", &source, location, &code));
+            Some(program.functions.len() as u32 - 1)
+        };
+        program.globals = environment.globals.len() as u32;
+        self.environment = environment;
+        Ok(function)
+    }
+}
+
+/// Returns whether a REPL entry ends inside of brackets, a string or a block comment.
+pub fn is_incomplete(source: Rc<Source>) -> bool {
+    let mut lexer = Lexer::new(source);
+    let mut depth = 0;
+    loop {
+        match lexer.peek_token() {
+            Ok(Some(token)) => match token.kind {
+                TokenKind::LeftParen | TokenKind::LeftSquare | TokenKind::LeftCurly => depth += 1,
+                TokenKind::RightParen | TokenKind::RightSquare | TokenKind::RightCurly => {
+                    depth -= 1
+                }
+                _ => (),
+            },
+            Ok(None) => return depth > 0,
+            Err(Error::Lexer(err)) => {
+                return matches!(
+                    err.kind(),
+                    LexerErrorKind::InvalidEof | LexerErrorKind::UnterminatedComment
+                )
+            }
+            Err(_) => return false,
+        }
+        if lexer.consume_token().is_err() {
+            return false;
+        }
+    }
+}
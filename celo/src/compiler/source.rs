@@ -1,4 +1,4 @@
-use std::{io, ops::Index, rc::Rc};
+use std::{cell::OnceCell, collections::HashMap, fmt, io, ops::Index, rc::Rc};
 
 use super::error::{Error, Result};
 
@@ -7,28 +7,71 @@ pub struct SourceError {
     path: Rc<str>,
     kind: SourceErrorKind,
 }
-#[derive(Debug)]
 
+impl SourceError {
+    pub fn path(&self) -> &Rc<str> {
+        &self.path
+    }
+
+    pub fn kind(&self) -> &SourceErrorKind {
+        &self.kind
+    }
+}
+
+#[derive(Debug)]
 pub enum SourceErrorKind {
     FileNotFound,
     PermissionDenied,
     IoError(std::io::Error),
 }
 
+impl fmt::Display for SourceErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceErrorKind::FileNotFound => write!(f, "file not found"),
+            SourceErrorKind::PermissionDenied => write!(f, "permission denied"),
+            SourceErrorKind::IoError(err) => write!(f, "could not read file: {err}"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Source {
     pub path: Rc<str>,
     pub content: Rc<str>,
+    /// Byte offsets of the line starts, built on first use
+    line_starts: OnceCell<Vec<u32>>,
+}
+
+/// Unit in which columns are counted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnMode {
+    /// Columns count bytes.
+    Utf8,
+    /// Columns count UTF-16 code units, as used by the language server protocol.
+    Utf16,
+    /// Columns count characters, like the columns of a [Location].
+    Chars,
+}
+
+/// A 1-based line and column, like the position of a [Location].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineColumn {
+    pub line: u32,
+    pub column: u32,
 }
 
 impl Source {
+    /// Loads a source from disk.
     pub fn load(path: impl Into<Rc<str>>) -> Result<Rc<Self>> {
+        Self::load_with(&DiskFileProvider, path)
+    }
+
+    /// Loads a source through a file provider.
+    pub fn load_with(provider: &dyn FileProvider, path: impl Into<Rc<str>>) -> Result<Rc<Self>> {
         let path = path.into();
-        match std::fs::read_to_string(&*path) {
-            Ok(content) => Ok(Rc::new(Self {
-                path,
-                content: content.into(),
-            })),
+        match provider.read(&path) {
+            Ok(content) => Ok(Self::from_string(path, content)),
             Err(err) => Err(Error::Source(Box::new(SourceError {
                 path,
                 kind: match err.kind() {
@@ -39,6 +82,135 @@ impl Source {
             }))),
         }
     }
+
+    /// Creates a source from memory, for code that does not exist on disk.
+    pub fn from_string(path: impl Into<Rc<str>>, content: impl Into<Rc<str>>) -> Rc<Self> {
+        Rc::new(Self {
+            path: path.into(),
+            content: content.into(),
+            line_starts: OnceCell::new(),
+        })
+    }
+
+    /// Returns the byte offsets at which lines start.
+    pub fn line_starts(&self) -> &[u32] {
+        self.line_starts.get_or_init(|| {
+            let newlines = self.content.match_indices('\n').map(|(i, _)| i as u32 + 1);
+            std::iter::once(0).chain(newlines).collect()
+        })
+    }
+
+    /// Converts a byte offset into a line and column.
+    ///
+    /// Offsets inside of a character are rounded down, offsets past the end are clamped.
+    pub fn line_column(&self, offset: u32, mode: ColumnMode) -> LineColumn {
+        let mut offset = (offset as usize).min(self.content.len());
+        while !self.content.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line_starts = self.line_starts();
+        let line = line_starts.partition_point(|&start| start as usize <= offset) - 1;
+        let prefix = &self.content[line_starts[line] as usize..offset];
+        LineColumn {
+            line: line as u32 + 1,
+            column: column_width(prefix, mode) + 1,
+        }
+    }
+
+    /// Converts a line and column into a byte offset.
+    ///
+    /// Columns past the end of the line are clamped to the line end. Returns `None` if the line
+    /// does not exist.
+    pub fn offset(&self, position: LineColumn, mode: ColumnMode) -> Option<u32> {
+        let line_starts = self.line_starts();
+        let line = (position.line as usize).checked_sub(1)?;
+        let start = *line_starts.get(line)? as usize;
+        let end = line_starts
+            .get(line + 1)
+            .map_or(self.content.len(), |&end| end as usize);
+        let text = self.content[start..end].trim_end_matches(['\n', '\r']);
+        let mut remaining = position.column.saturating_sub(1);
+        for (i, c) in text.char_indices() {
+            let width = column_width(&text[i..i + c.len_utf8()], mode);
+            if remaining < width {
+                return Some((start + i) as u32);
+            }
+            remaining -= width;
+        }
+        Some((start + text.len()) as u32)
+    }
+
+    /// Returns the start and end position of a location.
+    pub fn range(&self, location: Location, mode: ColumnMode) -> (LineColumn, LineColumn) {
+        (
+            self.line_column(location.start, mode),
+            self.line_column(location.end, mode),
+        )
+    }
+}
+
+fn column_width(text: &str, mode: ColumnMode) -> u32 {
+    match mode {
+        ColumnMode::Utf8 => text.len() as u32,
+        ColumnMode::Utf16 => text.chars().map(|c| c.len_utf16() as u32).sum(),
+        ColumnMode::Chars => text.chars().count() as u32,
+    }
+}
+
+/// Provides the content of source files to the compiler.
+pub trait FileProvider {
+    fn read(&self, path: &str) -> io::Result<Rc<str>>;
+}
+
+/// Reads source files from disk.
+pub struct DiskFileProvider;
+
+impl FileProvider for DiskFileProvider {
+    fn read(&self, path: &str) -> io::Result<Rc<str>> {
+        std::fs::read_to_string(path).map(Into::into)
+    }
+}
+
+/// Serves source files from memory, e.g. unsaved editor buffers.
+///
+/// Paths that are not in memory are read from the fallback provider, if there is one.
+#[derive(Default)]
+pub struct MemoryFileProvider {
+    files: HashMap<Rc<str>, Rc<str>>,
+    fallback: Option<Box<dyn FileProvider>>,
+}
+
+impl MemoryFileProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fallback(fallback: impl FileProvider + 'static) -> Self {
+        Self {
+            files: HashMap::new(),
+            fallback: Some(Box::new(fallback)),
+        }
+    }
+
+    pub fn insert(&mut self, path: impl Into<Rc<str>>, content: impl Into<Rc<str>>) {
+        self.files.insert(path.into(), content.into());
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.files.remove(path);
+    }
+}
+
+impl FileProvider for MemoryFileProvider {
+    fn read(&self, path: &str) -> io::Result<Rc<str>> {
+        if let Some(content) = self.files.get(path) {
+            return Ok(content.clone());
+        }
+        match &self.fallback {
+            Some(fallback) => fallback.read(path),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Default)]
@@ -80,6 +252,8 @@ pub enum TokenKind {
     Integer,
     Float,
     String,
+    Char,
+    FormatString,
     // Brackets
     LeftParen,
     RightParen,
@@ -93,4 +267,69 @@ pub enum TokenKind {
     BangIdentifier,
     // Keywords
     RightArrow,
+    If,
+    Else,
+    Try,
+    Catch,
+    /// Keyword registered in a [`KeywordTable`](super::lexer::KeywordTable)
+    Keyword,
+    Comment,
+}
+
+impl TokenKind {
+    /// Returns the name of the kind, which identifies it in machine-readable output.
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenKind::Integer => "Integer",
+            TokenKind::Float => "Float",
+            TokenKind::String => "String",
+            TokenKind::Char => "Char",
+            TokenKind::FormatString => "FormatString",
+            TokenKind::LeftParen => "LeftParen",
+            TokenKind::RightParen => "RightParen",
+            TokenKind::LeftSquare => "LeftSquare",
+            TokenKind::RightSquare => "RightSquare",
+            TokenKind::LeftCurly => "LeftCurly",
+            TokenKind::RightCurly => "RightCurly",
+            TokenKind::Identifier => "Identifier",
+            TokenKind::DotIdentifier => "DotIdentifier",
+            TokenKind::BangIdentifier => "BangIdentifier",
+            TokenKind::RightArrow => "RightArrow",
+            TokenKind::If => "If",
+            TokenKind::Else => "Else",
+            TokenKind::Try => "Try",
+            TokenKind::Catch => "Catch",
+            TokenKind::Keyword => "Keyword",
+            TokenKind::Comment => "Comment",
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TokenKind::Integer => "integer",
+            TokenKind::Float => "float",
+            TokenKind::String => "string",
+            TokenKind::Char => "character",
+            TokenKind::FormatString => "format string",
+            TokenKind::LeftParen => "`(`",
+            TokenKind::RightParen => "`)`",
+            TokenKind::LeftSquare => "`[`",
+            TokenKind::RightSquare => "`]`",
+            TokenKind::LeftCurly => "`{`",
+            TokenKind::RightCurly => "`}`",
+            TokenKind::Identifier => "identifier",
+            TokenKind::DotIdentifier => "variable",
+            TokenKind::BangIdentifier => "macro",
+            TokenKind::RightArrow => "`->`",
+            TokenKind::If => "`if`",
+            TokenKind::Else => "`else`",
+            TokenKind::Try => "`try`",
+            TokenKind::Catch => "`catch`",
+            TokenKind::Keyword => "keyword",
+            TokenKind::Comment => "comment",
+        };
+        f.write_str(name)
+    }
 }
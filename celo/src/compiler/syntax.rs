@@ -0,0 +1,299 @@
+use std::{fmt, mem, rc::Rc};
+
+use super::{
+    error::Result,
+    lexer::Lexer,
+    source::{Location, Source, Token, TokenKind},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+}
+
+/// Source text that carries no meaning for the compiler.
+#[derive(Clone, Copy, Debug)]
+pub struct Trivia {
+    pub location: Location,
+    pub kind: TriviaKind,
+}
+
+/// Token with the trivia around it.
+///
+/// Trailing trivia runs up to the end of the token's line, everything after belongs to the
+/// leading trivia of the next token.
+#[derive(Clone, Debug)]
+pub struct SyntaxToken {
+    pub leading: Vec<Trivia>,
+    pub token: Token,
+    pub trailing: Vec<Trivia>,
+}
+
+/// Lossless token stream of a source, concatenating all tokens and trivia gives back the source.
+///
+/// The compiler does not need trivia and uses the [`Lexer`] directly.
+#[derive(Debug)]
+pub struct TokenStream {
+    pub source: Rc<Source>,
+    pub tokens: Vec<SyntaxToken>,
+    /// Trivia after the line of the last token
+    pub end: Vec<Trivia>,
+}
+
+impl TokenStream {
+    pub fn new(source: Rc<Source>) -> Result<Self> {
+        let mut lexer = Lexer::with_comments(source.clone());
+        let mut tokens: Vec<SyntaxToken> = Vec::new();
+        let mut pending = Vec::new();
+        // Set while trivia still belongs to the line of the last token
+        let mut trailing = false;
+        let mut cursor = Cursor::default();
+        loop {
+            let token = lexer.peek_token()?;
+            lexer.consume_token()?;
+            let gap_end = token.map_or(source.content.len() as u32, |token| token.location.start);
+            let mut whitespace = cursor.advance_to(&source, gap_end);
+            if let (true, Some(gap)) = (trailing, whitespace) {
+                let text = &source[gap];
+                if let Some(newline) = text.find('\n') {
+                    let (same_line, rest) = split(gap, text, newline);
+                    let last = tokens.last_mut().expect("trailing trivia follows a token");
+                    last.trailing.extend(same_line.map(whitespace_trivia));
+                    whitespace = Some(rest);
+                    trailing = false;
+                }
+            }
+            let whitespace = whitespace.map(whitespace_trivia);
+            match (trailing, tokens.last_mut()) {
+                (true, Some(last)) => last.trailing.extend(whitespace),
+                _ => pending.extend(whitespace),
+            }
+            let Some(token) = token else {
+                break;
+            };
+            cursor.advance_to(&source, token.location.end);
+            if token.kind == TokenKind::Comment {
+                let comment = Trivia {
+                    location: token.location,
+                    kind: TriviaKind::Comment,
+                };
+                match (trailing, tokens.last_mut()) {
+                    (true, Some(last)) => last.trailing.push(comment),
+                    _ => pending.push(comment),
+                }
+                continue;
+            }
+            tokens.push(SyntaxToken {
+                leading: mem::take(&mut pending),
+                token,
+                trailing: Vec::new(),
+            });
+            trailing = true;
+        }
+        Ok(Self {
+            source,
+            tokens,
+            end: pending,
+        })
+    }
+}
+
+impl fmt::Display for TokenStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.tokens {
+            write_token(f, &self.source, token)?;
+        }
+        write_trivia(f, &self.source, &self.end)
+    }
+}
+
+#[derive(Debug)]
+pub enum SyntaxNode {
+    Token(SyntaxToken),
+    /// Nodes between matching brackets. The closing bracket is missing if the source ends first.
+    Delimited {
+        open: SyntaxToken,
+        children: Vec<SyntaxNode>,
+        close: Option<SyntaxToken>,
+    },
+}
+
+/// Lossless concrete syntax tree that nests tokens by brackets.
+///
+/// Closing brackets that do not match the innermost open bracket are kept as plain tokens.
+#[derive(Debug)]
+pub struct SyntaxTree {
+    pub source: Rc<Source>,
+    pub nodes: Vec<SyntaxNode>,
+    pub end: Vec<Trivia>,
+}
+
+impl SyntaxTree {
+    pub fn new(source: Rc<Source>) -> Result<Self> {
+        Ok(Self::from(TokenStream::new(source)?))
+    }
+}
+
+impl From<TokenStream> for SyntaxTree {
+    fn from(stream: TokenStream) -> Self {
+        // Open brackets with the nodes parsed since
+        let mut open: Vec<(SyntaxToken, Vec<SyntaxNode>)> = Vec::new();
+        let mut nodes = Vec::new();
+        for token in stream.tokens {
+            let closing = match token.token.kind {
+                TokenKind::LeftParen | TokenKind::LeftSquare | TokenKind::LeftCurly => {
+                    open.push((token, mem::take(&mut nodes)));
+                    continue;
+                }
+                TokenKind::RightParen => TokenKind::LeftParen,
+                TokenKind::RightSquare => TokenKind::LeftSquare,
+                TokenKind::RightCurly => TokenKind::LeftCurly,
+                _ => {
+                    nodes.push(SyntaxNode::Token(token));
+                    continue;
+                }
+            };
+            match open.pop() {
+                Some((opening, parent)) if opening.token.kind == closing => {
+                    let children = mem::replace(&mut nodes, parent);
+                    nodes.push(SyntaxNode::Delimited {
+                        open: opening,
+                        children,
+                        close: Some(token),
+                    });
+                }
+                unmatched => {
+                    open.extend(unmatched);
+                    nodes.push(SyntaxNode::Token(token));
+                }
+            }
+        }
+        while let Some((opening, parent)) = open.pop() {
+            let children = mem::replace(&mut nodes, parent);
+            nodes.push(SyntaxNode::Delimited {
+                open: opening,
+                children,
+                close: None,
+            });
+        }
+        Self {
+            source: stream.source,
+            nodes,
+            end: stream.end,
+        }
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_nodes(
+            f: &mut fmt::Formatter<'_>,
+            source: &Source,
+            nodes: &[SyntaxNode],
+        ) -> fmt::Result {
+            for node in nodes {
+                match node {
+                    SyntaxNode::Token(token) => write_token(f, source, token)?,
+                    SyntaxNode::Delimited {
+                        open,
+                        children,
+                        close,
+                    } => {
+                        write_token(f, source, open)?;
+                        write_nodes(f, source, children)?;
+                        if let Some(close) = close {
+                            write_token(f, source, close)?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        write_nodes(f, &self.source, &self.nodes)?;
+        write_trivia(f, &self.source, &self.end)
+    }
+}
+
+fn write_token(f: &mut fmt::Formatter<'_>, source: &Source, token: &SyntaxToken) -> fmt::Result {
+    write_trivia(f, source, &token.leading)?;
+    f.write_str(&source[token.token.location])?;
+    write_trivia(f, source, &token.trailing)
+}
+
+fn write_trivia(f: &mut fmt::Formatter<'_>, source: &Source, trivia: &[Trivia]) -> fmt::Result {
+    trivia
+        .iter()
+        .try_for_each(|trivia| f.write_str(&source[trivia.location]))
+}
+
+fn whitespace_trivia(location: Location) -> Trivia {
+    Trivia {
+        location,
+        kind: TriviaKind::Whitespace,
+    }
+}
+
+/// Splits a location at a byte offset into its text, the first part is `None` if empty.
+fn split(location: Location, text: &str, at: usize) -> (Option<Location>, Location) {
+    let middle = location.start + at as u32;
+    let first = Location {
+        end: middle,
+        ..location
+    };
+    let (line, column) = advance(location.line, location.column, &text[..at]);
+    let second = Location {
+        start: middle,
+        end: location.end,
+        line,
+        column,
+    };
+    ((at > 0).then_some(first), second)
+}
+
+/// Position in the source, counting columns in characters like the lexer.
+struct Cursor {
+    offset: u32,
+    line: u32,
+    column: u32,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Cursor {
+    /// Moves the cursor forward and returns the location of the skipped text, if any.
+    fn advance_to(&mut self, source: &Source, offset: u32) -> Option<Location> {
+        if offset <= self.offset {
+            return None;
+        }
+        let location = Location {
+            start: self.offset,
+            end: offset,
+            line: self.line,
+            column: self.column,
+        };
+        (self.line, self.column) = advance(self.line, self.column, &source[location]);
+        self.offset = offset;
+        Some(location)
+    }
+}
+
+fn advance(mut line: u32, mut column: u32, text: &str) -> (u32, u32) {
+    for c in text.chars() {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
@@ -0,0 +1,164 @@
+use std::{fmt::Write, path::Path};
+
+use crate::compiler::{effect::StackEffect, hir};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl DocFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            DocFormat::Markdown => "md",
+            DocFormat::Html => "html",
+        }
+    }
+}
+
+/// Generated documentation file.
+#[derive(Debug)]
+pub struct Page {
+    /// File name relative to the output directory
+    pub file_name: String,
+    pub content: String,
+}
+
+/// Generates a page per module and an index page linking them.
+///
+/// `effects` holds the stack effects of all functions in the order of the HIR, as returned by
+/// [`crate::compiler::effect::infer`]. Module names are their paths relative to `root`.
+pub fn generate(
+    hir: &hir::Hir,
+    effects: &[Option<StackEffect>],
+    root: &Path,
+    format: DocFormat,
+) -> Vec<Page> {
+    let mut pages = Vec::new();
+    let mut modules = Vec::new();
+    let mut effects = effects.iter().copied();
+    for module in &hir.modules {
+        let path = Path::new(&*module.source.path);
+        let name = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .with_extension("")
+            .to_string_lossy()
+            .replace(['/', '\\'], ".");
+        let file_name = format!("{name}.{}", format.extension());
+        let functions: Vec<(&hir::Function, Option<StackEffect>)> = module
+            .functions
+            .iter()
+            .map(|function| (&**function, effects.next().flatten()))
+            .collect();
+        let content = match format {
+            DocFormat::Markdown => markdown_module(&name, module, &functions),
+            DocFormat::Html => html_module(&name, module, &functions),
+        };
+        pages.push(Page {
+            file_name: file_name.clone(),
+            content,
+        });
+        modules.push((name, file_name));
+    }
+    let content = match format {
+        DocFormat::Markdown => markdown_index(&modules),
+        DocFormat::Html => html_index(&modules),
+    };
+    pages.push(Page {
+        file_name: format!("index.{}", format.extension()),
+        content,
+    });
+    pages
+}
+
+fn markdown_module(
+    name: &str,
+    module: &hir::Module,
+    functions: &[(&hir::Function, Option<StackEffect>)],
+) -> String {
+    let mut page = format!("# Module `{name}`\n");
+    for &(function, effect) in functions {
+        let function_name = &module.source[function.name];
+        _ = write!(page, "\n## `{function_name}`\n");
+        if let Some(effect) = effect {
+            _ = write!(page, "\n`{effect}`\n");
+        }
+        if let Some(doc) = &function.doc {
+            _ = write!(page, "\n{doc}\n");
+        }
+    }
+    page
+}
+
+fn markdown_index(modules: &[(String, String)]) -> String {
+    let mut page = String::from("# Modules\n\n");
+    for (name, file_name) in modules {
+        _ = writeln!(page, "- [`{name}`]({file_name})");
+    }
+    page
+}
+
+fn html_module(
+    name: &str,
+    module: &hir::Module,
+    functions: &[(&hir::Function, Option<StackEffect>)],
+) -> String {
+    let name = escape_html(name);
+    let mut page = html_header(&name);
+    _ = writeln!(page, "<h1>Module <code>{name}</code></h1>");
+    for &(function, effect) in functions {
+        let function_name = escape_html(&module.source[function.name]);
+        _ = write!(
+            page,
+            "<section id=\"fn.{function_name}\">\n<h2><code>{function_name}</code></h2>\n"
+        );
+        if let Some(effect) = effect {
+            _ = writeln!(page, "<p><code>{effect}</code></p>");
+        }
+        if let Some(doc) = &function.doc {
+            for paragraph in doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+                _ = writeln!(page, "<p>{}</p>", escape_html(paragraph.trim()));
+            }
+        }
+        page.push_str("</section>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+fn html_index(modules: &[(String, String)]) -> String {
+    let mut page = html_header("Modules");
+    page.push_str("<h1>Modules</h1>\n<ul>\n");
+    for (name, file_name) in modules {
+        _ = writeln!(
+            page,
+            "<li><a href=\"{}\"><code>{}</code></a></li>",
+            escape_html(file_name),
+            escape_html(name)
+        );
+    }
+    page.push_str("</ul>\n</body>\n</html>\n");
+    page
+}
+
+fn html_header(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n"
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
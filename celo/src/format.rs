@@ -0,0 +1,247 @@
+use std::{mem, rc::Rc};
+
+use crate::compiler::{
+    error::Result,
+    parser::{make_error, ParserErrorKind},
+    source::{Source, Token, TokenKind},
+    syntax::{TokenStream, Trivia, TriviaKind},
+};
+
+const INDENT: &str = "    ";
+
+/// Reprints a source file in the canonical style.
+///
+/// Scopes `{ }` are always split over lines and indented, groups `( )` and `[ ]` only if they
+/// already span multiple lines. Line breaks inside of scopes are kept, blank lines collapsed and
+/// every `fn!` is separated from its neighbours by a blank line.
+pub fn format(source: &Rc<Source>) -> Result<String> {
+    let tokens = tokenize(source)?;
+    let closers = match_brackets(source, &tokens)?;
+    let mut formatter = Formatter {
+        source,
+        tokens: &tokens,
+        closers: &closers,
+        output: String::new(),
+        indent: 0,
+        groups: Vec::new(),
+        root_macro: None,
+    };
+    for index in 0..tokens.len() {
+        formatter.token(index);
+    }
+    if !formatter.output.is_empty() {
+        formatter.output.push('\n');
+    }
+    Ok(formatter.output)
+}
+
+/// Token with the number of line breaks between it and the previous token.
+struct Spaced {
+    token: Token,
+    newlines: usize,
+}
+
+/// Flattens the token stream into tokens and comments, keeping the line breaks between them.
+fn tokenize(source: &Rc<Source>) -> Result<Vec<Spaced>> {
+    let stream = TokenStream::new(source.clone())?;
+    let mut tokens = Vec::new();
+    let mut newlines = 0;
+    let mut trivia_tokens = |tokens: &mut Vec<Spaced>, trivia: &[Trivia]| {
+        for trivia in trivia {
+            if trivia.kind == TriviaKind::Whitespace {
+                newlines += source[trivia.location].matches('\n').count();
+                continue;
+            }
+            let token = Token {
+                location: trivia.location,
+                kind: TokenKind::Comment,
+            };
+            tokens.push(Spaced { token, newlines });
+            newlines = 0;
+        }
+        mem::take(&mut newlines)
+    };
+    for syntax_token in &stream.tokens {
+        let newlines = trivia_tokens(&mut tokens, &syntax_token.leading);
+        tokens.push(Spaced {
+            token: syntax_token.token,
+            newlines,
+        });
+        trivia_tokens(&mut tokens, &syntax_token.trailing);
+    }
+    trivia_tokens(&mut tokens, &stream.end);
+    Ok(tokens)
+}
+
+/// Returns the index of the closing bracket for every opening bracket.
+fn match_brackets(source: &Rc<Source>, tokens: &[Spaced]) -> Result<Vec<Option<usize>>> {
+    let mut closers = vec![None; tokens.len()];
+    let mut open: Vec<(usize, TokenKind)> = Vec::new();
+    for (index, spaced) in tokens.iter().enumerate() {
+        let token = spaced.token;
+        let expected = match token.kind {
+            TokenKind::LeftParen => TokenKind::RightParen,
+            TokenKind::LeftSquare => TokenKind::RightSquare,
+            TokenKind::LeftCurly => TokenKind::RightCurly,
+            TokenKind::RightParen | TokenKind::RightSquare | TokenKind::RightCurly => {
+                let Some((opening, expected)) = open.pop() else {
+                    return Err(make_error(
+                        source,
+                        Some(token.location),
+                        ParserErrorKind::UnexpectedToken {
+                            expected: None,
+                            got: token.kind,
+                        },
+                    ));
+                };
+                if token.kind != expected {
+                    return Err(make_error(
+                        source,
+                        Some(token.location),
+                        ParserErrorKind::UnmatchedBracket {
+                            opening_bracket: tokens[opening].token.location,
+                            expected,
+                            got: Some(token.kind),
+                        },
+                    ));
+                }
+                closers[opening] = Some(index);
+                continue;
+            }
+            _ => continue,
+        };
+        open.push((index, expected));
+    }
+    if let Some((opening, expected)) = open.pop() {
+        return Err(make_error(
+            source,
+            None,
+            ParserErrorKind::UnmatchedBracket {
+                opening_bracket: tokens[opening].token.location,
+                expected,
+                got: None,
+            },
+        ));
+    }
+    Ok(closers)
+}
+
+enum Separator {
+    None,
+    Space,
+    Newline,
+    BlankLine,
+}
+
+struct Formatter<'a> {
+    source: &'a Source,
+    tokens: &'a [Spaced],
+    closers: &'a [Option<usize>],
+    output: String,
+    indent: usize,
+    /// Whether each open bracket spans multiple lines
+    groups: Vec<bool>,
+    /// Name of the last macro at the root level
+    root_macro: Option<&'a str>,
+}
+
+impl<'a> Formatter<'a> {
+    fn token(&mut self, index: usize) {
+        let token = self.tokens[index].token;
+        let text = self.source[token.location].trim_end();
+        let separator = self.separator(index);
+        match separator {
+            Separator::None => {}
+            Separator::Space => self.output.push(' '),
+            Separator::Newline | Separator::BlankLine => {
+                if matches!(separator, Separator::BlankLine) {
+                    self.output.push('\n');
+                }
+                self.output.push('\n');
+                self.output.push_str(&INDENT.repeat(self.indent));
+            }
+        }
+        self.output.push_str(text);
+        if self.groups.is_empty() && token.kind == TokenKind::BangIdentifier {
+            self.root_macro = Some(text);
+        }
+        if let Some(closer) = self.closers[index] {
+            let multiline = token.kind == TokenKind::LeftCurly
+                || self.tokens[index + 1..=closer].iter().any(|spaced| {
+                    spaced.newlines > 0
+                        || matches!(spaced.token.kind, TokenKind::Comment | TokenKind::LeftCurly)
+                });
+            self.groups.push(multiline);
+            if multiline {
+                self.indent += 1;
+            }
+        }
+    }
+
+    fn separator(&mut self, index: usize) -> Separator {
+        let Some(previous) = index.checked_sub(1).map(|index| &self.tokens[index]) else {
+            return Separator::None;
+        };
+        let spaced = &self.tokens[index];
+        let kind = spaced.token.kind;
+        let preserved = if spaced.newlines > 1 {
+            Separator::BlankLine
+        } else {
+            Separator::Newline
+        };
+        if matches!(
+            kind,
+            TokenKind::RightParen | TokenKind::RightSquare | TokenKind::RightCurly
+        ) {
+            let multiline = self.groups.pop().expect("matched brackets");
+            if !multiline {
+                return Separator::None;
+            }
+            self.indent -= 1;
+            if self.closers[index - 1] == Some(index) {
+                return Separator::None;
+            }
+            return Separator::Newline;
+        }
+        if previous.token.kind == TokenKind::Comment {
+            // Only block comments can be followed by code on the same line
+            if spaced.newlines == 0 {
+                return Separator::Space;
+            }
+            return preserved;
+        }
+        if self.closers[index - 1].is_some() {
+            return match self.groups.last() {
+                Some(true) => Separator::Newline,
+                _ => Separator::None,
+            };
+        }
+        if kind == TokenKind::Comment {
+            if spaced.newlines == 0 {
+                return Separator::Space;
+            }
+            // Comments on their own line at the root start a new paragraph
+            if self.groups.is_empty() {
+                return Separator::BlankLine;
+            }
+            return preserved;
+        }
+        if self.groups.is_empty() && kind == TokenKind::BangIdentifier {
+            let text = &self.source[spaced.token.location];
+            return match self.root_macro {
+                Some(previous) if previous == text && text != "fn!" => Separator::Newline,
+                _ => Separator::BlankLine,
+            };
+        }
+        if matches!(
+            kind,
+            TokenKind::LeftCurly | TokenKind::Else | TokenKind::Catch
+        ) {
+            return Separator::Space;
+        }
+        if spaced.newlines == 0 || self.groups.is_empty() {
+            return Separator::Space;
+        }
+        preserved
+    }
+}
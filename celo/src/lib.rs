@@ -1,3 +1,5 @@
 #![allow(dead_code)] // todo - remove
 
 pub mod compiler;
+pub mod doc;
+pub mod format;
@@ -0,0 +1,60 @@
+use celo::{
+    compiler::{
+        error::Error,
+        lexer::{Lexer, LexerErrorKind},
+        source::{Source, TokenKind},
+        Compiler,
+    },
+    format::format,
+};
+
+#[test]
+fn skips_nested_block_comments() {
+    let code = "1 ;( a ;( nested ); still ( comment \n ); 2 ;();3";
+    let source = Source::from_string("test.celo", code);
+    let mut lexer = Lexer::new(source.clone());
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.peek_token().unwrap() {
+        tokens.push(&source[token.location]);
+        lexer.consume_token().unwrap();
+    }
+    assert_eq!(tokens, ["1", "2", "3"]);
+
+    let mut lexer = Lexer::with_comments(source.clone());
+    lexer.consume_token().unwrap();
+    let comment = lexer.peek_token().unwrap().unwrap();
+    assert_eq!(comment.kind, TokenKind::Comment);
+    assert_eq!(
+        &source[comment.location],
+        ";( a ;( nested ); still ( comment \n );"
+    );
+}
+
+#[test]
+fn unterminated_block_comment_is_an_error() {
+    let code = "fn! main {}\n;( ;( inner );\nfn! old {}";
+    let source = Source::from_string("test.celo", code);
+    let Err(Error::Lexer(err)) = Compiler::new(source).compile() else {
+        panic!("unterminated comment should not lex");
+    };
+    assert!(matches!(err.kind(), LexerErrorKind::UnterminatedComment));
+    assert_eq!((err.location().line, err.location().column), (2, 1));
+}
+
+#[test]
+fn comments_out_functions() {
+    let code = "fn! main { 1 print }\n;(\nfn! main { broken ( }\n);\n";
+    let source = Source::from_string("test.celo", code);
+    Compiler::new(source.clone()).compile().unwrap();
+    let formatted = format(&source).unwrap();
+    assert_eq!(
+        formatted,
+        "fn! main {\n    1 print\n}\n\n;(\nfn! main { broken ( }\n);\n"
+    );
+    let inline = format(&Source::from_string(
+        "test.celo",
+        "fn! main { 1 ;( two ); 3 }",
+    ))
+    .unwrap();
+    assert_eq!(inline, "fn! main {\n    1 ;( two ); 3\n}\n");
+}
@@ -0,0 +1,27 @@
+use celo::compiler::{
+    error::Error,
+    lexer::{Lexer, LexerErrorKind},
+    source::{Source, TokenKind},
+};
+
+/// Lexes code into the kind and text of each token.
+pub fn lex(code: &str) -> Result<Vec<(TokenKind, String)>, Error> {
+    let source = Source::from_string("test.celo", code);
+    let mut lexer = Lexer::new(source.clone());
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.peek_token()? {
+        tokens.push((token.kind, source[token.location].to_string()));
+        lexer.consume_token()?;
+    }
+    Ok(tokens)
+}
+
+/// Asserts that each code fails to lex with the given kind of error.
+pub fn assert_lexer_errors(cases: &[(&str, LexerErrorKind)]) {
+    for (code, expected) in cases {
+        let Err(Error::Lexer(err)) = lex(code) else {
+            panic!("{code} should not lex");
+        };
+        assert_eq!(err.kind(), expected, "{code}");
+    }
+}
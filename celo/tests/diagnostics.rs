@@ -0,0 +1,64 @@
+use std::rc::Rc;
+
+use celo::compiler::{
+    diagnostic::{Diagnostic, Severity},
+    source::{LineColumn, MemoryFileProvider, Source},
+    Compiler,
+};
+
+fn check(code: &str) -> Diagnostic {
+    let source = Source::from_string("test.celo", code);
+    let err = Compiler::with_file_provider(source, Rc::new(MemoryFileProvider::new()))
+        .check()
+        .unwrap_err();
+    Diagnostic::from(&err)
+}
+
+#[test]
+fn points_to_related_locations() {
+    let diagnostic = check("fn! main {\n  (1 2 ]\n}");
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(
+        (diagnostic.code, diagnostic.name),
+        ("C0001", "UnmatchedBracket")
+    );
+    let span = diagnostic.span.unwrap();
+    assert_eq!((span.location.start, span.location.end), (18, 19));
+    assert_eq!(span.start, LineColumn { line: 2, column: 8 });
+    assert_eq!(span.end, LineColumn { line: 2, column: 9 });
+    let [related] = &diagnostic.related[..] else {
+        panic!("the opening bracket should be related");
+    };
+    assert_eq!(related.span.start, LineColumn { line: 2, column: 3 });
+
+    let diagnostic = check("fn! twice {}\nfn! twice {}");
+    assert_eq!(diagnostic.code, "C0201");
+    assert_eq!(diagnostic.span.unwrap().start.line, 2);
+    assert_eq!(diagnostic.related[0].span.start.line, 1);
+}
+
+#[test]
+fn counts_columns_in_characters() {
+    let diagnostic = check("fn! main { \"héllo\" print $ }");
+    assert_eq!(diagnostic.code, "C0101");
+    let span = diagnostic.span.unwrap();
+    assert_eq!(
+        span.start,
+        LineColumn {
+            line: 1,
+            column: 26
+        }
+    );
+    assert_eq!(span.location.start, 26);
+}
+
+#[test]
+fn does_not_require_main() {
+    let source = Source::from_string("test.celo", "fn! helper { 1 }\ntest! it { helper }");
+    Compiler::new(source).check().unwrap();
+
+    let diagnostic = check("import! \"missing.celo\"");
+    assert_eq!(diagnostic.code, "C0301");
+    assert_eq!(&*diagnostic.path, "missing.celo");
+    assert!(diagnostic.span.is_none());
+}
@@ -0,0 +1,64 @@
+use std::{path::Path, rc::Rc};
+
+use celo::{
+    compiler::{
+        effect, lowering::LowerMirStep, source::MemoryFileProvider, source::Source, Compiler,
+    },
+    doc::{generate, DocFormat},
+};
+
+fn compiler() -> Compiler {
+    let mut provider = MemoryFileProvider::new();
+    provider.insert(
+        "lib/math.celo",
+        ";; Squares a number.\n;;\n;; Works for floats too.\nfn! square { dup * }\n",
+    );
+    let main = Source::from_string(
+        "main.celo",
+        "import! \"lib/math.celo\"\n; not documentation\nfn! main { 3 square print }\n",
+    );
+    Compiler::with_file_provider(main, Rc::new(provider))
+}
+
+#[test]
+fn attaches_doc_comments_to_functions() {
+    let hir = compiler().parse().unwrap();
+    assert_eq!(hir.modules[0].functions[0].doc, None);
+    assert_eq!(
+        hir.modules[1].functions[0].doc.as_deref(),
+        Some("Squares a number.\n\nWorks for floats too.")
+    );
+}
+
+#[test]
+fn detached_comments_are_not_documentation() {
+    let code = ";; Header of the file.\n\nfn! a {}\n;; Stale.\n; Plain comment\nfn! b {}\n;; Kept.\nfn! c {}\n";
+    let hir = Compiler::new(Source::from_string("main.celo", code))
+        .parse()
+        .unwrap();
+    let docs: Vec<_> = hir.modules[0]
+        .functions
+        .iter()
+        .map(|function| function.doc.as_deref())
+        .collect();
+    assert_eq!(docs, [None, None, Some("Kept.")]);
+}
+
+#[test]
+fn generates_markdown_per_module() {
+    let hir = compiler().parse().unwrap();
+    let (mir, _) = LowerMirStep::new(&hir).run_incremental().unwrap();
+    let pages = generate(
+        &hir,
+        &effect::infer(&mir),
+        Path::new(""),
+        DocFormat::Markdown,
+    );
+    let names: Vec<&str> = pages.iter().map(|page| page.file_name.as_str()).collect();
+    assert_eq!(names, ["main.md", "lib.math.md", "index.md"]);
+    assert_eq!(
+        pages[1].content,
+        "# Module `lib.math`\n\n## `square`\n\n`( 1 -- 1 )`\n\nSquares a number.\n\nWorks for floats too.\n"
+    );
+    assert!(pages[2].content.contains("- [`main`](main.md)"));
+}
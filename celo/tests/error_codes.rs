@@ -0,0 +1,88 @@
+use std::{collections::HashSet, rc::Rc};
+
+use celo::compiler::{
+    error::{find_error_code, ERROR_CODES},
+    lexer::LexerErrorKind,
+    lowering::LoweringErrorKind,
+    parser::ParserErrorKind,
+    source::{MemoryFileProvider, Source},
+    Compiler,
+};
+
+/// Returns the `celo` code blocks of a Markdown text.
+fn examples(explanation: &str) -> Vec<&str> {
+    explanation
+        .split("```")
+        .skip(1)
+        .step_by(2)
+        .filter_map(|block| block.strip_prefix("celo\n"))
+        .collect()
+}
+
+#[test]
+fn codes_are_unique() {
+    let codes: HashSet<_> = ERROR_CODES.iter().map(|code| code.code).collect();
+    let names: HashSet<_> = ERROR_CODES.iter().map(|code| code.name).collect();
+    assert_eq!(codes.len(), ERROR_CODES.len());
+    assert_eq!(names.len(), ERROR_CODES.len());
+    for code in ERROR_CODES {
+        let digits = code.code.strip_prefix('C').unwrap();
+        assert!(digits.len() == 4 && digits.chars().all(|c| c.is_ascii_digit()));
+        assert!(!code.explanation.trim().is_empty(), "{}", code.code);
+    }
+}
+
+#[test]
+fn looks_up_codes_and_names() {
+    assert_eq!(find_error_code("C0001").unwrap().name, "UnmatchedBracket");
+    assert_eq!(find_error_code("c0001").unwrap().name, "UnmatchedBracket");
+    assert_eq!(find_error_code("UnknownFunction").unwrap().code, "C0205");
+    assert!(find_error_code("C9999").is_none());
+}
+
+#[test]
+fn kinds_know_their_code() {
+    let kinds = [
+        (
+            ParserErrorKind::UnknownMacro.code(),
+            ParserErrorKind::UnknownMacro.name(),
+        ),
+        (
+            LexerErrorKind::InvalidEof.code(),
+            LexerErrorKind::InvalidEof.name(),
+        ),
+        (
+            LoweringErrorKind::AmbiguousFunction.code(),
+            LoweringErrorKind::AmbiguousFunction.name(),
+        ),
+    ];
+    assert_eq!(
+        kinds,
+        [
+            ("C0005", "UnknownMacro"),
+            ("C0102", "InvalidEof"),
+            ("C0208", "AmbiguousFunction"),
+        ]
+    );
+}
+
+#[test]
+fn examples_match_their_code() {
+    for code in ERROR_CODES {
+        let examples = examples(code.explanation);
+        let erroneous = code.explanation.contains("Erroneous code example");
+        for (i, example) in examples.iter().enumerate() {
+            let source = Source::from_string("example.celo", *example);
+            let file_provider = Rc::new(MemoryFileProvider::new());
+            let mut compiler = Compiler::with_file_provider(source, file_provider);
+            // Tests are only lowered when compiling them
+            let result = compiler.compile_tests().and_then(|_| compiler.compile());
+            match result {
+                Err(err) if erroneous && i == 0 => assert_eq!(err.code(), code.code),
+                Ok(_) if !erroneous || i > 0 => {}
+                Err(err) => panic!("example {i} of {}: {err}", code.code),
+                Ok(_) => panic!("example {i} of {} compiles", code.code),
+            }
+        }
+    }
+}
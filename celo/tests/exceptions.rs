@@ -0,0 +1,57 @@
+use celo::compiler::{source::Source, Compiler};
+use maquina::vm::{error::RuntimeErrorKind, value::Value, Vm};
+
+fn run(content: &str) -> Vm {
+    let source = Source::from_string("exceptions.celo", content);
+    let program = Compiler::new(source).compile().expect("compile program");
+    let mut vm = Vm::new(program);
+    vm.run().expect("run program");
+    vm
+}
+
+fn display_stack(vm: &Vm) -> Vec<String> {
+    vm.stack()
+        .iter()
+        .map(|&value| vm.heap().display(value))
+        .collect()
+}
+
+#[test]
+fn catches_runtime_errors() {
+    let vm = run(r#"
+        fn! divide { / }
+        fn! main {
+            1 try { 2 3 0 divide "unreachable" } catch { -> .err "caught: " .err concat }
+        }
+    "#);
+    assert_eq!(display_stack(&vm), ["1", "caught: division by zero"]);
+}
+
+#[test]
+fn catches_thrown_values_across_frames() {
+    let vm = run(r#"
+        fn! fail { map "code" 42 set throw }
+        fn! nested { try { fail } catch { throw } }
+        fn! main {
+            try { nested } catch { "code" get }
+            try { 1 } catch { 2 }
+        }
+    "#);
+    assert_eq!(vm.stack(), [Value::Integer(42), Value::Integer(1)]);
+}
+
+#[test]
+fn catches_failed_io() {
+    let vm = run(r#"
+        fn! main { try { "/nonexistent/celo.txt" read-file } catch { drop "missing" } }
+    "#);
+    assert_eq!(display_stack(&vm), ["missing"]);
+}
+
+#[test]
+fn reports_uncaught_exceptions() {
+    let source = Source::from_string("exceptions.celo", r#"fn! main { "oops" throw }"#);
+    let program = Compiler::new(source).compile().expect("compile program");
+    let err = Vm::new(program).run().expect_err("uncaught exception");
+    assert!(matches!(err.kind, RuntimeErrorKind::Thrown { message, .. } if message == "oops"));
+}
@@ -0,0 +1,43 @@
+use celo::{
+    compiler::{error::Error, source::Source},
+    format::format,
+};
+
+fn format_str(code: &str) -> String {
+    format(&Source::from_string("test.celo", code)).expect("format source")
+}
+
+#[test]
+fn formats_canonically() {
+    let code = concat!(
+        "import! \"a.celo\"\nimport!   \"b.celo\"\n",
+        "; entry point\nfn! main { 1 2 + print   ; trailing\n",
+        "  ( 1 2 ) [ 3\n 4 ]\n\n\n  if { 1 } else { 2 }\n",
+        "} fn! empty {}\n",
+    );
+    let expected = concat!(
+        "import! \"a.celo\"\nimport! \"b.celo\"\n\n",
+        "; entry point\nfn! main {\n    1 2 + print ; trailing\n",
+        "    (1 2) [\n        3\n        4\n    ]\n\n",
+        "    if {\n        1\n    } else {\n        2\n    }\n}\n\n",
+        "fn! empty {}\n",
+    );
+    assert_eq!(format_str(code), expected);
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let code = "fn! main {\n  try { 1 0 / }\n  catch { drop ; ignore\n }\n}";
+    let formatted = format_str(code);
+    assert_eq!(
+        formatted,
+        "fn! main {\n    try {\n        1 0 /\n    } catch {\n        drop ; ignore\n    }\n}\n"
+    );
+    assert_eq!(format_str(&formatted), formatted);
+}
+
+#[test]
+fn rejects_unmatched_brackets() {
+    let source = Source::from_string("test.celo", "fn! main { (1 2 }");
+    assert!(matches!(format(&source), Err(Error::Parser(_))));
+}
@@ -0,0 +1,123 @@
+//! Compiles every `.celo` file in `tests/golden` and compares its HIR and MIR dumps, its output
+//! and its diagnostics with the files of the same name. Set `UPDATE_GOLDEN=1` to rewrite them.
+
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use celo::compiler::{codegen, lowering::LowerMirStep, source::Source, Compiler};
+use maquina::vm::Vm;
+
+const FIXTURES: &str = "tests/golden";
+
+/// Output of `print`, shared with the test.
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Outcome {
+    hir: String,
+    mir: String,
+    stdout: String,
+    stderr: String,
+}
+
+fn run_fixture(path: &Path) -> Outcome {
+    let mut outcome = Outcome::default();
+    let source = match Source::load(path.to_string_lossy()) {
+        Ok(source) => source,
+        Err(err) => {
+            outcome.stderr = format!("{err}\n");
+            return outcome;
+        }
+    };
+    let hir = match Compiler::new(source).parse() {
+        Ok(hir) => hir,
+        Err(err) => {
+            outcome.stderr = format!("{err}\n");
+            return outcome;
+        }
+    };
+    outcome.hir = hir.to_string();
+    let mir = match LowerMirStep::new(&hir).run() {
+        Ok(mir) => mir,
+        Err(err) => {
+            outcome.stderr = format!("{err}\n");
+            return outcome;
+        }
+    };
+    outcome.mir = mir.to_string();
+    let capture = Capture::default();
+    let mut vm = Vm::new(codegen::generate(&mir));
+    vm.set_output(capture.clone());
+    if let Err(err) = vm.run() {
+        outcome.stderr = format!("{err}\n");
+    }
+    outcome.stdout = String::from_utf8_lossy(&capture.0.borrow()).into();
+    outcome
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(FIXTURES)
+        .expect("read fixtures")
+        .map(|entry| entry.expect("read fixture").path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "celo")
+        })
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+#[test]
+fn golden_files() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some_and(|value| value != "0");
+    let mut failures = Vec::new();
+    for fixture in fixtures() {
+        let outcome = run_fixture(&fixture);
+        let outputs = [
+            ("hir", outcome.hir),
+            ("mir", outcome.mir),
+            ("stdout", outcome.stdout),
+            ("stderr", outcome.stderr),
+        ];
+        for (extension, actual) in outputs {
+            let path = fixture.with_extension(extension);
+            let expected = fs::read_to_string(&path).unwrap_or_default();
+            if actual == expected {
+                continue;
+            }
+            if !update {
+                failures.push(format!(
+                    "{} differs\n--- expected\n{expected}--- actual\n{actual}",
+                    path.display()
+                ));
+            } else if actual.is_empty() {
+                fs::remove_file(&path).expect("remove golden file");
+            } else {
+                fs::write(&path, actual).expect("write golden file");
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\n\nrun with UPDATE_GOLDEN=1 to accept the changes",
+        failures.join("\n")
+    );
+}
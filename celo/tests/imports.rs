@@ -0,0 +1,88 @@
+use std::rc::Rc;
+
+use celo::compiler::{
+    error::Error,
+    lowering::LoweringErrorKind,
+    source::{MemoryFileProvider, Source},
+    Compiler,
+};
+use maquina::vm::{value::Value, Vm};
+
+fn provider() -> MemoryFileProvider {
+    let mut provider = MemoryFileProvider::new();
+    provider.insert(
+        "lib/math.celo",
+        "import! \"util.celo\"\nfn! square { dup * }",
+    );
+    provider.insert("lib/util.celo", "import! \"math.celo\"\nfn! inc { 1 + }");
+    provider
+}
+
+#[test]
+fn compiles_imports_from_memory() {
+    let main = Source::from_string(
+        "main.celo",
+        "import! \"lib/math.celo\"\nfn! main { 3 square inc }",
+    );
+    let program = Compiler::with_file_provider(main, Rc::new(provider()))
+        .compile()
+        .expect("compile program");
+    let mut vm = Vm::new(program);
+    vm.run().expect("run program");
+    assert_eq!(vm.stack(), [Value::Integer(10)]);
+}
+
+#[test]
+fn reports_missing_imports() {
+    let main = Source::from_string("main.celo", "import! \"missing.celo\"\nfn! main {}");
+    let result = Compiler::with_file_provider(main, Rc::new(provider())).compile();
+    assert!(matches!(result, Err(Error::Source(_))));
+}
+
+#[test]
+fn loads_each_file_once() {
+    let main = Source::from_string(
+        "main.celo",
+        "import! \"lib/math.celo\"\nimport! \"./lib/util.celo\"\nimport! \"lib/../lib/util.celo\"\nfn! main { 3 square inc }",
+    );
+    let hir = Compiler::with_file_provider(main, Rc::new(provider()))
+        .parse()
+        .expect("parse program");
+    let paths: Vec<_> = hir
+        .modules
+        .iter()
+        .map(|module| &*module.source.path)
+        .collect();
+    assert_eq!(paths, ["main.celo", "lib/math.celo", "lib/util.celo"]);
+}
+
+#[test]
+fn reports_ambiguous_imports() {
+    let provider = Rc::new({
+        let mut provider = provider();
+        provider.insert("other.celo", "fn! inc { 2 + }");
+        provider
+    });
+    let main = Source::from_string(
+        "main.celo",
+        "import! \"lib/util.celo\"\nimport! \"other.celo\"\nfn! main { 1 inc }",
+    );
+    let result = Compiler::with_file_provider(main, provider.clone()).compile();
+    let Err(Error::Lowering(err)) = result else {
+        panic!("expected a lowering error");
+    };
+    assert!(matches!(err.kind(), LoweringErrorKind::AmbiguousFunction));
+    assert_eq!(&*err.source().path, "main.celo");
+
+    // A function of the calling module takes precedence
+    let main = Source::from_string(
+        "main.celo",
+        "import! \"lib/util.celo\"\nimport! \"other.celo\"\nfn! inc { 3 + }\nfn! main { 1 inc }",
+    );
+    let program = Compiler::with_file_provider(main, provider)
+        .compile()
+        .expect("compile program");
+    let mut vm = Vm::new(program);
+    vm.run().expect("run program");
+    assert_eq!(vm.stack(), [Value::Integer(4)]);
+}
@@ -0,0 +1,229 @@
+use std::rc::Rc;
+
+use celo::compiler::{
+    hir,
+    incremental::{ChangeKind, Document, FunctionChange, TextEdit},
+    source::{MemoryFileProvider, Source},
+    Compiler,
+};
+
+const CODE: &str = "\
+;; Adds one
+fn! inc { 1 + }
+
+fn! main {
+    \"héllo\" print
+    (2 3) + inc print
+}
+
+test! works { 1 inc 2 = assert }
+
+fn! last { 3 -> .x f\"{.x}!\" print }
+";
+
+fn new_document(code: &str) -> (Compiler, Document) {
+    let source = Source::from_string("test.celo", code);
+    let compiler = Compiler::new(source.clone());
+    let document = Document::new(&compiler, source);
+    (compiler, document)
+}
+
+/// Debug output of everything a document holds, which includes all locations.
+fn snapshot(document: &Document) -> String {
+    format!(
+        "{:?}\n{:?}\n{:?}\n{:?}",
+        document.tokens().collect::<Vec<_>>(),
+        document.functions().collect::<Vec<_>>(),
+        document.tests().collect::<Vec<_>>(),
+        document.error().map(|err| err.location()),
+    )
+}
+
+fn changed(name: &str, kind: ChangeKind) -> FunctionChange {
+    FunctionChange {
+        name: name.into(),
+        test: false,
+        kind,
+    }
+}
+
+#[test]
+fn reparses_only_the_edited_function() {
+    let (compiler, mut document) = new_document(CODE);
+    assert!(document.error().is_none());
+    let start = CODE.find("(2 3)").unwrap() as u32 + 1;
+    let update = document.edit(
+        &compiler,
+        &TextEdit {
+            start,
+            end: start + 1,
+            text: "20".into(),
+        },
+    );
+    assert_eq!(update.changes, [changed("main", ChangeKind::Changed)]);
+    assert_eq!((update.reparsed, update.reused), (1, 3));
+    assert!(update.relexed.start > 0);
+    assert!((update.relexed.end as usize) < CODE.len());
+
+    let (_, fresh) = new_document(&document.source().content);
+    assert_eq!(snapshot(&document), snapshot(&fresh));
+}
+
+#[test]
+fn reports_added_and_removed_functions() {
+    let (compiler, mut document) = new_document(CODE);
+    let start = CODE.find("fn! inc").unwrap() as u32;
+    let update = document.edit(
+        &compiler,
+        &TextEdit {
+            start: start + 4,
+            end: start + 7,
+            text: "dec".into(),
+        },
+    );
+    assert_eq!(
+        update.changes,
+        [
+            changed("dec", ChangeKind::Added),
+            changed("inc", ChangeKind::Removed),
+        ]
+    );
+    let doc = document.functions().next().unwrap().doc.as_deref();
+    assert_eq!(doc, Some("Adds one"));
+
+    let end = CODE.len() as u32;
+    let update = document.edit(
+        &compiler,
+        &TextEdit {
+            start: end,
+            end,
+            text: "fn! extra {}\n".into(),
+        },
+    );
+    assert_eq!(update.changes, [changed("extra", ChangeKind::Added)]);
+    assert_eq!((update.reparsed, update.reused), (2, 3));
+}
+
+#[test]
+fn reports_changed_tests() {
+    let (compiler, mut document) = new_document(CODE);
+    let start = CODE.find("2 = assert").unwrap() as u32;
+    let update = document.edit(
+        &compiler,
+        &TextEdit {
+            start,
+            end: start + 1,
+            text: "3".into(),
+        },
+    );
+    let works = FunctionChange {
+        name: "works".into(),
+        test: true,
+        kind: ChangeKind::Changed,
+    };
+    assert_eq!(update.changes, [works]);
+
+    let start = CODE.find("test! works").unwrap() as u32;
+    let update = document.edit(
+        &compiler,
+        &TextEdit {
+            start,
+            end: start + 5,
+            text: "fn!".into(),
+        },
+    );
+    let kinds: Vec<_> = update
+        .changes
+        .iter()
+        .map(|change| (change.name.as_str(), change.test, change.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("works", false, ChangeKind::Added),
+            ("works", true, ChangeKind::Removed),
+        ]
+    );
+}
+
+#[test]
+fn recovers_from_lexer_errors() {
+    let (compiler, mut document) = new_document(CODE);
+    let start = CODE.find("\"héllo\"").unwrap() as u32;
+    document.edit(
+        &compiler,
+        &TextEdit {
+            start,
+            end: start + 1,
+            text: String::new(),
+        },
+    );
+    assert!(document.error().is_some());
+    document.edit(
+        &compiler,
+        &TextEdit {
+            start,
+            end: start,
+            text: "\"".into(),
+        },
+    );
+    assert!(document.error().is_none());
+    let (_, fresh) = new_document(CODE);
+    assert_eq!(snapshot(&document), snapshot(&fresh));
+}
+
+#[test]
+fn matches_a_fresh_parse_after_every_edit() {
+    let (compiler, _) = new_document(CODE);
+    let offsets = CODE.char_indices().map(|(i, _)| i as u32);
+    for start in offsets.chain([CODE.len() as u32]) {
+        let next = CODE[start as usize..].chars().next();
+        let mut edits = vec![TextEdit {
+            start,
+            end: start,
+            text: "x\n".into(),
+        }];
+        for text in ["", "}", "\"", "!"] {
+            if let Some(c) = next {
+                edits.push(TextEdit {
+                    start,
+                    end: start + c.len_utf8() as u32,
+                    text: text.into(),
+                });
+            }
+        }
+        for edit in edits {
+            let (_, mut document) = new_document(CODE);
+            document.edit(&compiler, &edit);
+            let (_, fresh) = new_document(&document.source().content);
+            assert_eq!(snapshot(&document), snapshot(&fresh), "{edit:?}");
+        }
+    }
+}
+
+#[test]
+fn lends_functions_as_a_module() {
+    let mut file_provider = MemoryFileProvider::new();
+    file_provider.insert("lib/math.celo", "fn! square { dup * }");
+    let code = format!("import! \"math.celo\"\n{CODE}");
+    let source = Source::from_string("lib/main.celo", code);
+    let compiler = Compiler::with_file_provider(source.clone(), Rc::new(file_provider));
+    let mut document = Document::new(&compiler, source.clone());
+    let imports: Vec<&str> = document.imports().map(|path| &**path).collect();
+    assert_eq!(imports, ["lib/math.celo"]);
+
+    let before = snapshot(&document);
+    let (functions, tests) = document.with_module(|module| {
+        assert!(Rc::ptr_eq(&module.source, &source));
+        let names = |functions: &[Box<hir::Function>]| -> Vec<String> {
+            functions
+                .iter()
+                .map(|function| source[function.name].to_string())
+                .collect()
+        };
+        (names(&module.functions), names(&module.tests))
+    });
+    assert_eq!(functions, ["inc", "main", "last"]);
+    assert_eq!(tests, ["works"]);
+    assert_eq!(snapshot(&document), before);
+}
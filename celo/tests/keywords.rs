@@ -0,0 +1,85 @@
+use std::rc::Rc;
+
+use celo::compiler::{
+    error::Error,
+    hir,
+    lexer::{KeywordTable, Lexer},
+    source::{MemoryFileProvider, Source, TokenKind},
+    Compiler,
+};
+
+fn new_compiler(files: &[(&str, &str)]) -> Compiler {
+    let mut file_provider = MemoryFileProvider::new();
+    for &(path, content) in files {
+        file_provider.insert(path, content);
+    }
+    let source = Source::from_string(files[0].0, files[0].1);
+    Compiler::with_file_provider(source, Rc::new(file_provider))
+}
+
+#[test]
+fn only_keyword_kinds_can_be_registered() {
+    let mut keywords = KeywordTable::new();
+    for kind in [TokenKind::String, TokenKind::Integer, TokenKind::Identifier] {
+        assert!(!keywords.insert("word", kind), "{kind:?}");
+    }
+    assert_eq!(keywords.get("word"), None);
+    assert!(keywords.insert("word", TokenKind::Try));
+    assert_eq!(keywords.get("word"), Some(TokenKind::Try));
+}
+
+#[test]
+fn built_in_keywords_cannot_be_overridden() {
+    let mut keywords = KeywordTable::new();
+    assert!(!keywords.insert("if", TokenKind::Keyword));
+    assert!(keywords.insert("|>", TokenKind::Keyword));
+    assert_eq!(keywords.get("if"), Some(TokenKind::If));
+    assert_eq!(keywords.get("|>"), Some(TokenKind::Keyword));
+    assert_eq!(keywords.get("pipe"), None);
+    assert!(keywords.keywords().any(|keyword| keyword == "|>"));
+
+    let source = Source::from_string("test.celo", "1 |> if");
+    let mut lexer = Lexer::with_keywords(source, Rc::new(keywords));
+    let mut kinds = Vec::new();
+    while let Some(token) = lexer.peek_token().unwrap() {
+        kinds.push(token.kind);
+        lexer.consume_token().unwrap();
+    }
+    assert_eq!(
+        kinds,
+        [TokenKind::Integer, TokenKind::Keyword, TokenKind::If]
+    );
+}
+
+#[test]
+fn aliases_apply_to_all_modules() {
+    let mut compiler = new_compiler(&[
+        (
+            "main.celo",
+            "import! \"lib.celo\"\nfn! main { 1 when { 2 } }",
+        ),
+        ("lib.celo", "fn! f { 0 when { 1 } otherwise { 2 } }"),
+    ]);
+    compiler.keywords_mut().insert("when", TokenKind::If);
+    compiler.keywords_mut().insert("otherwise", TokenKind::Else);
+    let hir = compiler.parse().unwrap();
+    for module in &hir.modules {
+        let code = &module.functions[0].body.code;
+        assert!(matches!(code[1].kind, hir::NodeKind::If(_)));
+    }
+    // Other compilations are not affected
+    let mut compiler = new_compiler(&[("main.celo", "fn! main { 1 when }")]);
+    assert!(compiler.compile().is_err());
+}
+
+#[test]
+fn reserved_words_are_not_identifiers() {
+    for code in ["fn! main { match }", "fn! match { 1 }"] {
+        let mut compiler = new_compiler(&[("main.celo", code)]);
+        compiler.keywords_mut().insert("match", TokenKind::Keyword);
+        let Err(Error::Parser(err)) = compiler.parse() else {
+            panic!("{code} should not parse");
+        };
+        assert!(err.kind().to_string().contains("keyword"), "{code}");
+    }
+}
@@ -0,0 +1,53 @@
+use celo::compiler::source::{ColumnMode, LineColumn, Location, Source};
+
+fn position(line: u32, column: u32) -> LineColumn {
+    LineColumn { line, column }
+}
+
+#[test]
+fn converts_offsets_to_line_columns() {
+    let source = Source::from_string("test.celo", "ab\nüx 𝄞y\r\n\nend");
+    assert_eq!(source.line_starts(), [0, 3, 14, 15]);
+    assert_eq!(source.line_column(0, ColumnMode::Utf8), position(1, 1));
+    assert_eq!(source.line_column(2, ColumnMode::Utf8), position(1, 3));
+    assert_eq!(source.line_column(3, ColumnMode::Utf8), position(2, 1));
+    // `x` follows the two byte `ü`
+    assert_eq!(source.line_column(5, ColumnMode::Utf8), position(2, 3));
+    assert_eq!(source.line_column(5, ColumnMode::Utf16), position(2, 2));
+    // `y` follows the four byte, two code unit `𝄞`
+    assert_eq!(source.line_column(11, ColumnMode::Utf8), position(2, 9));
+    assert_eq!(source.line_column(11, ColumnMode::Utf16), position(2, 6));
+    // Offsets inside of a character are rounded down
+    assert_eq!(source.line_column(4, ColumnMode::Utf16), position(2, 1));
+    assert_eq!(source.line_column(100, ColumnMode::Utf8), position(4, 4));
+}
+
+#[test]
+fn converts_line_columns_to_offsets() {
+    let source = Source::from_string("test.celo", "ab\nüx 𝄞y\r\n\nend");
+    for offset in [0, 2, 3, 5, 6, 7, 11, 14, 15, 18] {
+        for mode in [ColumnMode::Utf8, ColumnMode::Utf16] {
+            let position = source.line_column(offset, mode);
+            assert_eq!(source.offset(position, mode), Some(offset), "{position:?}");
+        }
+    }
+    // Columns past the line end are clamped before the line break
+    assert_eq!(source.offset(position(2, 50), ColumnMode::Utf16), Some(12));
+    assert_eq!(source.offset(position(5, 1), ColumnMode::Utf8), None);
+    assert_eq!(source.offset(position(0, 1), ColumnMode::Utf8), None);
+}
+
+#[test]
+fn converts_location_ranges() {
+    let source = Source::from_string("test.celo", "fn! main {\n  1\n}");
+    let location = Location {
+        start: 9,
+        end: 16,
+        line: 1,
+        column: 10,
+    };
+    assert_eq!(
+        source.range(location, ColumnMode::Utf8),
+        (position(1, 10), position(3, 2))
+    );
+}
@@ -0,0 +1,99 @@
+use celo::compiler::{
+    error::Error,
+    hir::{self, MacroIntermediate},
+    lowering::{LowerMirStep, LoweringErrorKind},
+    mir::InstructionKind,
+    source::Source,
+    Compiler,
+};
+use maquina::vm::{intrinsic::Intrinsic, value::Value, Vm};
+
+const COLLECTIONS: &str = r#"
+fn! main {
+    array 1 push 2 push -> .list
+    map "key" .list set -> .table
+    .table "key" get len
+    .table "key" has
+    "a" "b" concat
+}
+"#;
+
+#[derive(Debug)]
+struct Unexpanded;
+
+impl MacroIntermediate for Unexpanded {}
+
+#[test]
+fn lowers_collections_to_intrinsics() {
+    let source = Source::from_string("collections.celo", COLLECTIONS);
+    let hir = Compiler::new(source).parse().expect("parse program");
+    let mir = LowerMirStep::new(&hir).run().expect("lower program");
+    let main = &mir.functions[mir.entry.unwrap()];
+    let intrinsics: Vec<_> = main
+        .body
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction.kind {
+            InstructionKind::Intrinsic(intrinsic) => Some(intrinsic),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        intrinsics,
+        [
+            Intrinsic::Array,
+            Intrinsic::Push,
+            Intrinsic::Push,
+            Intrinsic::Map,
+            Intrinsic::Set,
+            Intrinsic::Get,
+            Intrinsic::Len,
+            Intrinsic::Has,
+            Intrinsic::Concat,
+        ]
+    );
+    let strings: Vec<_> = main
+        .body
+        .instructions
+        .iter()
+        .filter_map(|instruction| match &instruction.kind {
+            InstructionKind::String(string) => Some(&**string),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(strings, ["key", "key", "key", "a", "b"]);
+}
+
+#[test]
+fn runs_collections() {
+    let source = Source::from_string("collections.celo", COLLECTIONS);
+    let program = Compiler::new(source).compile().expect("compile program");
+    let mut vm = Vm::new(program);
+    vm.run().expect("run program");
+    let &[len, has, string] = vm.stack() else {
+        panic!("expected three values on the stack");
+    };
+    assert_eq!((len, has), (Value::Integer(2), Value::Integer(1)));
+    assert_eq!(vm.heap().display(string), "ab");
+}
+
+#[test]
+fn reports_unexpanded_macros() {
+    let source = Source::from_string("macro.celo", "fn! main { 1 }");
+    let mut hir = Compiler::new(source).parse().expect("parse program");
+    let body = &mut hir.modules[0].functions[0].body;
+    let location = body.code[0].location;
+    let node = hir::Node::new(
+        location,
+        hir::NodeKind::MacroIntermediate(Box::new(Unexpanded)),
+    );
+    body.code.push(node);
+    let Err(Error::Lowering(err)) = LowerMirStep::new(&hir).run() else {
+        panic!("expected a lowering error");
+    };
+    assert!(matches!(err.kind(), LoweringErrorKind::UnexpandedMacro));
+    assert_eq!(
+        err.location().map(|location| location.start),
+        Some(location.start)
+    );
+}
@@ -0,0 +1,72 @@
+use celo::compiler::{
+    lexer::{parse_float, parse_integer, LexerErrorKind},
+    source::TokenKind,
+};
+
+mod common;
+
+use common::{assert_lexer_errors, lex};
+
+#[test]
+fn lexes_number_literals() {
+    let cases = [
+        ("0x1F", TokenKind::Integer),
+        ("-0xff_u8", TokenKind::Integer),
+        ("0b1010_1010", TokenKind::Integer),
+        ("0o777", TokenKind::Integer),
+        ("1_000_000", TokenKind::Integer),
+        ("10u8", TokenKind::Integer),
+        ("-5i32", TokenKind::Integer),
+        ("1.5e-3", TokenKind::Float),
+        ("2E10", TokenKind::Float),
+        ("3.0f32", TokenKind::Float),
+        ("7f64", TokenKind::Float),
+        ("1_0.2_5", TokenKind::Float),
+    ];
+    for (code, kind) in cases {
+        assert_eq!(lex(code).unwrap(), [(kind, code.to_string())], "{code}");
+    }
+    let tokens: Vec<TokenKind> = lex("1 2.5 -3").unwrap().into_iter().map(|t| t.0).collect();
+    assert_eq!(
+        tokens,
+        [TokenKind::Integer, TokenKind::Float, TokenKind::Integer]
+    );
+}
+
+#[test]
+fn rejects_malformed_number_literals() {
+    let cases = [
+        ("0b102", LexerErrorKind::InvalidDigit),
+        ("0o8", LexerErrorKind::InvalidDigit),
+        ("0x1.5", LexerErrorKind::InvalidDigit),
+        ("0x", LexerErrorKind::MissingDigits),
+        ("0b_", LexerErrorKind::MissingDigits),
+        ("1e", LexerErrorKind::MissingExponent),
+        ("1.5e+", LexerErrorKind::MissingExponent),
+        ("10u7", LexerErrorKind::InvalidNumberSuffix),
+        ("1.5u8", LexerErrorKind::InvalidNumberSuffix),
+        ("0b1f32", LexerErrorKind::InvalidNumberSuffix),
+        ("12abc", LexerErrorKind::InvalidNumberSuffix),
+    ];
+    assert_lexer_errors(&cases);
+}
+
+#[test]
+fn evaluates_number_literals() {
+    assert_eq!(parse_integer("0x1F"), Some(31));
+    assert_eq!(parse_integer("-0b101"), Some(-5));
+    assert_eq!(parse_integer("0o17"), Some(15));
+    assert_eq!(parse_integer("1_000u16"), Some(1000));
+    assert_eq!(parse_integer("-128i8"), Some(-128));
+    assert_eq!(parse_integer("256u8"), None);
+    assert_eq!(parse_integer("-1u32"), None);
+    assert_eq!(parse_integer("9223372036854775808"), None);
+    assert_eq!(parse_integer("-9223372036854775808"), Some(i64::MIN));
+    assert_eq!(parse_integer("18446744073709551615u64"), Some(-1));
+    assert_eq!(parse_integer("0x8000_0000_0000_0000u64"), Some(i64::MIN));
+    assert_eq!(parse_integer("18446744073709551616u64"), None);
+    assert_eq!(parse_integer("-1u64"), None);
+    assert_eq!(parse_float("1.5e-3"), 0.0015);
+    assert_eq!(parse_float("2_5.0f64"), 25.0);
+    assert_eq!(parse_float("0.1f32"), f64::from(0.1f32));
+}
@@ -0,0 +1,103 @@
+use std::rc::Rc;
+
+use celo::compiler::{
+    error::Error,
+    lexer::Lexer,
+    lowering::LowerMirStep,
+    source::{ColumnMode, MemoryFileProvider, Source},
+    Compiler,
+};
+use proptest::prelude::*;
+
+/// Source text biased towards celo syntax, with some multi-byte characters mixed in.
+fn code() -> impl Strategy<Value = String> {
+    proptest::collection::vec(
+        prop_oneof![
+            Just(" "),
+            Just("\n"),
+            Just("fn!"),
+            Just("main"),
+            Just("{"),
+            Just("}"),
+            Just("("),
+            Just(")"),
+            Just("["),
+            Just("]"),
+            Just("->"),
+            Just(".x"),
+            Just("if"),
+            Just("else"),
+            Just("try"),
+            Just("catch"),
+            Just("\""),
+            Just("\\"),
+            Just("'"),
+            Just("r#"),
+            Just("f\""),
+            Just("\\u{"),
+            Just(";"),
+            Just("-"),
+            Just("1"),
+            Just("."),
+            Just("ä"),
+            Just("🦀"),
+        ],
+        0..40,
+    )
+    .prop_map(|parts| parts.concat())
+}
+
+fn compile(content: &str) -> Result<(), Error> {
+    let source = Source::from_string("test.celo", content);
+    let mut compiler = Compiler::with_file_provider(source, Rc::new(MemoryFileProvider::new()));
+    let hir = compiler.parse()?;
+    LowerMirStep::new(&hir).run()?;
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn token_locations_are_valid(content in prop_oneof![code(), any::<String>()]) {
+        let source = Source::from_string("test.celo", content.as_str());
+        let mut lexer = Lexer::new(source.clone());
+        while let Ok(Some(token)) = lexer.peek_token() {
+            let location = token.location;
+            prop_assert!(location.start <= location.end);
+            prop_assert!(location.end as usize <= content.len());
+            prop_assert!(content.is_char_boundary(location.start as usize));
+            prop_assert!(content.is_char_boundary(location.end as usize));
+            let line_start = source.line_starts()[location.line as usize - 1] as usize;
+            let position = source.line_column(location.start, ColumnMode::Utf8);
+            prop_assert_eq!(position.line, location.line);
+            let column = content[line_start..location.start as usize].chars().count() as u32 + 1;
+            prop_assert_eq!(column, location.column);
+            lexer.consume_token().unwrap();
+        }
+    }
+
+    #[test]
+    fn compiling_never_panics(content in code()) {
+        _ = compile(&content);
+    }
+}
+
+#[test]
+fn unsupported_syntax_is_an_error() {
+    for code in [
+        "fn! main { [1] }",
+        "fn! main { { 1 } }",
+        "fn! main { fn! f {} }",
+        "fn! main { 1 else }",
+    ] {
+        let err = compile(code).unwrap_err();
+        assert!(matches!(err, Error::Parser(_)), "{code}: {err}");
+    }
+}
+
+#[test]
+fn deep_nesting_is_an_error() {
+    let depth = 10_000;
+    let code = format!("fn! main {{ {}{} }}", "(".repeat(depth), ")".repeat(depth));
+    let err = compile(&code).unwrap_err();
+    assert!(err.to_string().contains("nested deeper"), "{err}");
+}
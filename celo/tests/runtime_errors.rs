@@ -0,0 +1,34 @@
+use celo::compiler::{source::Source, Compiler};
+use maquina::vm::{
+    error::RuntimeErrorKind, intrinsic::Intrinsic, program::Instruction, value::Value, Vm,
+};
+
+#[test]
+fn division_by_zero_has_backtrace() {
+    let source = Source::from_string(
+        "divide.celo",
+        "fn! divide {\n    /\n}\n\nfn! main {\n    1 2 3 0 divide print\n}\n",
+    );
+    let program = Compiler::new(source).compile().expect("compile program");
+    let mut vm = Vm::new(program);
+    let err = vm.run().expect_err("division by zero");
+    assert_eq!(err.kind, RuntimeErrorKind::DivisionByZero);
+    assert!(matches!(
+        err.instruction,
+        Some(Instruction::Intrinsic(Intrinsic::Div))
+    ));
+    let trace: Vec<_> = err
+        .backtrace
+        .iter()
+        .map(|frame| {
+            let position = frame.position.as_ref().expect("debug info");
+            format!(
+                "{} {}:{}:{}",
+                frame.function, position.path, position.line, position.column
+            )
+        })
+        .collect();
+    assert_eq!(trace, ["divide divide.celo:2:5", "main divide.celo:6:13"]);
+    // The failed call is unwound with the operands of `/`, the rest stays on the stack
+    assert_eq!(vm.stack(), [Value::Integer(1), Value::Integer(2)]);
+}
@@ -0,0 +1,89 @@
+use celo::compiler::{
+    error::Error,
+    lowering::LoweringErrorKind,
+    session::{is_incomplete, Session},
+    source::Source,
+};
+use maquina::vm::{program::Program, value::Value, Vm};
+
+struct Repl {
+    session: Session,
+    vm: Vm,
+    entries: usize,
+}
+
+impl Repl {
+    fn new() -> Self {
+        Self {
+            session: Session::default(),
+            vm: Vm::new(Program::default()),
+            entries: 0,
+        }
+    }
+
+    /// Compiles and runs an entry, returning the stack afterwards.
+    fn eval(&mut self, entry: &str) -> Result<Vec<Value>, Error> {
+        self.entries += 1;
+        let source = Source::from_string(format!("<repl:{}>", self.entries), entry);
+        if let Some(function) = self.session.compile(source, self.vm.program_mut())? {
+            self.vm.call(function).expect("run entry");
+        }
+        Ok(self.vm.stack().to_vec())
+    }
+}
+
+#[test]
+fn variables_persist_as_globals() {
+    let mut repl = Repl::new();
+    repl.eval("1 2 + -> .x").unwrap();
+    assert_eq!(repl.vm.program().globals, 1);
+    repl.eval("10 -> .y").unwrap();
+    assert_eq!(repl.vm.program().globals, 2);
+    let stack = repl.eval(".x .y *").unwrap();
+    assert_eq!(stack, [Value::Integer(30)]);
+    assert_eq!(repl.vm.globals(), [Value::Integer(3), Value::Integer(10)]);
+}
+
+#[test]
+fn calls_functions_of_earlier_entries() {
+    let mut repl = Repl::new();
+    let functions = repl.vm.program().functions.len();
+    assert_eq!(repl.eval("fn! double { 2 * }").unwrap(), []);
+    assert_eq!(repl.vm.program().functions.len(), functions + 1);
+    assert_eq!(repl.eval("21 double").unwrap(), [Value::Integer(42)]);
+}
+
+#[test]
+fn failing_entries_leave_the_environment_untouched() {
+    let mut repl = Repl::new();
+    repl.eval("1 -> .x").unwrap();
+    let functions = repl.vm.program().functions.len();
+
+    let Err(Error::Lowering(err)) = repl.eval("2 -> .y .missing") else {
+        panic!("expected a lowering error");
+    };
+    assert!(matches!(err.kind(), LoweringErrorKind::UnknownVariable));
+    let Err(Error::Lowering(err)) = repl.eval("fn! broken { missing }") else {
+        panic!("expected a lowering error");
+    };
+    assert!(matches!(err.kind(), LoweringErrorKind::UnknownFunction));
+    assert_eq!(repl.vm.program().functions.len(), functions);
+    assert_eq!(repl.vm.program().globals, 1);
+
+    assert!(repl.eval(".y").is_err());
+    assert!(repl.eval("broken").is_err());
+    assert_eq!(repl.eval(".x").unwrap(), [Value::Integer(1)]);
+}
+
+#[test]
+fn detects_incomplete_entries() {
+    let incomplete = |entry: &str| is_incomplete(Source::from_string("<repl>", entry));
+    assert!(incomplete("fn! main {"));
+    assert!(incomplete("(1 2"));
+    assert!(incomplete("\"unclosed"));
+    assert!(incomplete("1 ;( comment"));
+    assert!(!incomplete("fn! main { 1 }"));
+    assert!(!incomplete("\"closed\" print"));
+    assert!(!incomplete("1 ;( comment ); 2"));
+    assert!(!incomplete("1 2 )"));
+}
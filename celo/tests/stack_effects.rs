@@ -0,0 +1,49 @@
+use std::rc::Rc;
+
+use celo::compiler::{
+    effect::{self, StackEffect},
+    lowering::LowerMirStep,
+    source::{MemoryFileProvider, Source},
+    Compiler,
+};
+
+fn effects(code: &str) -> Vec<Option<StackEffect>> {
+    let source = Source::from_string("main.celo", code);
+    let hir = Compiler::with_file_provider(source, Rc::new(MemoryFileProvider::new()))
+        .parse()
+        .expect("parse program");
+    let (mir, _) = LowerMirStep::new(&hir)
+        .run_incremental()
+        .expect("lower program");
+    effect::infer(&mir)
+}
+
+#[test]
+fn infers_straight_line_effects() {
+    let effects = effects("fn! square { dup * }\nfn! sum3 { + + }\nfn! pair { 1 2 }");
+    assert_eq!(effects[0], Some(StackEffect::new(1, 1)));
+    assert_eq!(effects[1], Some(StackEffect::new(3, 1)));
+    assert_eq!(effects[2], Some(StackEffect::new(0, 2)));
+    assert_eq!(effects[0].unwrap().to_string(), "( 1 -- 1 )");
+}
+
+#[test]
+fn infers_effects_through_calls_and_branches() {
+    let effects = effects(concat!(
+        "fn! inc { 1 + }\n",
+        "fn! abs { dup 0 < if { 0 swap - } }\n",
+        "fn! twice { inc inc }\n",
+        "fn! safe { try { 1 0 / } catch { drop 0 } }\n",
+        "fn! fail { \"no\" throw }\n",
+    ));
+    assert_eq!(effects[1], Some(StackEffect::new(1, 1)));
+    assert_eq!(effects[2], Some(StackEffect::new(1, 1)));
+    assert_eq!(effects[3], Some(StackEffect::new(0, 1)));
+    assert_eq!(effects[4], None);
+}
+
+#[test]
+fn rejects_unbalanced_branches_and_recursion() {
+    let effects = effects("fn! odd { if { 1 } else { 1 2 } }\nfn! loop { loop }");
+    assert_eq!(effects, [None, None]);
+}
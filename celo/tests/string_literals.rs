@@ -0,0 +1,94 @@
+use celo::compiler::{
+    lexer::{split_format_string, unescape, unescape_char, FormatPartKind, Lexer, LexerErrorKind},
+    source::{Source, TokenKind},
+};
+
+mod common;
+
+use common::{assert_lexer_errors, lex};
+
+#[test]
+fn lexes_extended_literals() {
+    let cases = [
+        (r#""\u{1F980} \x41\0""#, TokenKind::String),
+        (r#"r"C:\path""#, TokenKind::String),
+        (r###"r##"say "#hi"#"##"###, TokenKind::String),
+        ("\"\"\"\n    a\n    \"\"\"", TokenKind::String),
+        ("'a'", TokenKind::Char),
+        ("'\\n'", TokenKind::Char),
+        ("'\\u{e4}'", TokenKind::Char),
+        ("'🦀'", TokenKind::Char),
+    ];
+    for (code, kind) in cases {
+        assert_eq!(lex(code).unwrap(), [(kind, code.to_string())], "{code}");
+    }
+    let tokens = lex("r rot r#x").unwrap();
+    assert!(tokens
+        .iter()
+        .all(|(kind, _)| *kind == TokenKind::Identifier));
+}
+
+#[test]
+fn rejects_malformed_literals() {
+    let cases = [
+        (r#""\q""#, LexerErrorKind::InvalidEscapeSequence),
+        (r#""\x4""#, LexerErrorKind::InvalidEscapeSequence),
+        (r#""\u{110000}""#, LexerErrorKind::InvalidUnicodeEscape),
+        (r#""\u{D800}""#, LexerErrorKind::InvalidUnicodeEscape),
+        (r#""\u41""#, LexerErrorKind::InvalidUnicodeEscape),
+        (r#""\u{+41}""#, LexerErrorKind::InvalidUnicodeEscape),
+        ("r#\"open\"", LexerErrorKind::InvalidEof),
+        ("''", LexerErrorKind::InvalidCharLiteral),
+        ("'ab'", LexerErrorKind::InvalidCharLiteral),
+        ("'a", LexerErrorKind::InvalidEof),
+        ("\"\"\"text\n\"\"\"", LexerErrorKind::InvalidMultilineString),
+        (
+            "\"\"\"\n  a\n    \"\"\"",
+            LexerErrorKind::InvalidMultilineString,
+        ),
+        ("\"\"\"\n  a", LexerErrorKind::InvalidEof),
+    ];
+    assert_lexer_errors(&cases);
+}
+
+#[test]
+fn evaluates_literals() {
+    assert_eq!(unescape(r#""\u{1F980}\x41\xff\0""#), "🦀A\u{ff}\0");
+    assert_eq!(unescape(r#"r"C:\n""#), "C:\\n");
+    assert_eq!(unescape(r###"r##"a "# b"##"###), "a \"# b");
+    let multiline = "\"\"\"\n        first\n\n          second \\t\n        \"\"\"";
+    assert_eq!(unescape(multiline), "first\n\n  second \t");
+    assert_eq!(unescape("\"\"\"\n    \"\"\""), "");
+    assert_eq!(unescape_char("'\\''"), '\'');
+    assert_eq!(unescape_char("'ä'"), 'ä');
+}
+
+#[test]
+fn splits_format_strings() {
+    let code = "  f\"a {.x}{{b}} \\u{7B}\n{.y}\"";
+    let source = Source::from_string("test.celo", code);
+    let mut lexer = Lexer::new(source.clone());
+    let token = lexer.peek_token().unwrap().unwrap();
+    assert_eq!(token.kind, TokenKind::FormatString);
+    let parts: Vec<_> = split_format_string(&source, token.location)
+        .into_iter()
+        .map(|part| {
+            let location = part.location;
+            let text = &source[location];
+            (part.kind, text, location.line, location.column)
+        })
+        .collect();
+    assert_eq!(
+        parts,
+        [
+            (FormatPartKind::Text, "a ", 1, 5),
+            (FormatPartKind::Variable, ".x", 1, 8),
+            (FormatPartKind::Text, "{b", 1, 12),
+            (FormatPartKind::Text, "} \\u{7B}\n", 1, 15),
+            (FormatPartKind::Variable, ".y", 2, 2),
+        ]
+    );
+    let cases = ["f\"{x}\"", "f\"{.}\"", "f\"{.x\"", "f\"}\"", "f\"{ .x }\""]
+        .map(|code| (code, LexerErrorKind::InvalidInterpolation));
+    assert_lexer_errors(&cases);
+}
@@ -0,0 +1,61 @@
+use celo::compiler::{
+    source::{Source, TokenKind},
+    syntax::{SyntaxNode, SyntaxTree, TokenStream, TriviaKind},
+};
+
+const CODE: &str = "; header\n\nfn! main { ; start\n  1 2 +   ; sum\n  (3 \"ä\")\n}  \n; footer\n";
+
+#[test]
+fn round_trips_source() {
+    let stream = TokenStream::new(Source::from_string("test.celo", CODE)).unwrap();
+    assert_eq!(stream.to_string(), CODE);
+    let tree = SyntaxTree::new(Source::from_string("test.celo", CODE)).unwrap();
+    assert_eq!(tree.to_string(), CODE);
+    for code in ["", "  \n", "fn! main { (1 ]", "} 1"] {
+        let tree = SyntaxTree::new(Source::from_string("test.celo", code)).unwrap();
+        assert_eq!(tree.to_string(), code);
+    }
+}
+
+#[test]
+fn attaches_trivia_to_tokens() {
+    let source = Source::from_string("test.celo", CODE);
+    let stream = TokenStream::new(source.clone()).unwrap();
+    let text = |trivia: &[celo::compiler::syntax::Trivia]| -> Vec<String> {
+        trivia
+            .iter()
+            .map(|t| source[t.location].to_string())
+            .collect()
+    };
+
+    let first = &stream.tokens[0];
+    assert_eq!(&source[first.token.location], "fn!");
+    assert_eq!(text(&first.leading), ["; header", "\n\n"]);
+    assert_eq!(first.leading[0].kind, TriviaKind::Comment);
+
+    let curly = &stream.tokens[2];
+    assert_eq!(text(&curly.trailing), [" ", "; start"]);
+    let plus = &stream.tokens[5];
+    assert_eq!(text(&plus.trailing), ["   ", "; sum"]);
+    assert_eq!(plus.trailing[1].location.line, 4);
+    assert_eq!(plus.trailing[1].location.column, 11);
+
+    let last = stream.tokens.last().unwrap();
+    assert_eq!(last.token.kind, TokenKind::RightCurly);
+    assert_eq!(text(&last.trailing), ["  "]);
+    assert_eq!(text(&stream.end), ["\n", "; footer", "\n"]);
+}
+
+#[test]
+fn nests_tokens_by_brackets() {
+    let tree = SyntaxTree::new(Source::from_string("test.celo", CODE)).unwrap();
+    assert_eq!(tree.nodes.len(), 3);
+    let SyntaxNode::Delimited {
+        children, close, ..
+    } = &tree.nodes[2]
+    else {
+        panic!("expected function body");
+    };
+    assert!(close.is_some());
+    assert!(matches!(children[3], SyntaxNode::Delimited { .. }));
+}
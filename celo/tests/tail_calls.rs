@@ -0,0 +1,56 @@
+use celo::compiler::{source::Source, Compiler};
+use maquina::vm::{
+    error::{RuntimeError, RuntimeErrorKind},
+    value::Value,
+    Config, Vm,
+};
+
+const COUNTDOWN: &str = r#"
+fn! countdown {
+    -> .n
+    .n 0 = if { .n } else { .n 1 - countdown }
+}
+
+fn! sum {
+    -> .n -> .acc
+    .n 0 = if { .acc } else { .acc .n + .n 1 - sum }
+}
+
+fn! main {
+    1000000 countdown
+    0 1000000 sum
+}
+"#;
+
+fn run(content: &str, max_frames: usize) -> Result<Vm, RuntimeError> {
+    let source = Source::from_string("countdown.celo", content);
+    let program = Compiler::new(source).compile().expect("compile program");
+    let mut vm = Vm::with_config(
+        program,
+        Config {
+            max_frames,
+            ..Config::default()
+        },
+    );
+    vm.run()?;
+    Ok(vm)
+}
+
+#[test]
+fn tail_recursion_reuses_frames() {
+    let vm = run(COUNTDOWN, 4).expect("run program");
+    assert_eq!(
+        vm.stack(),
+        [Value::Integer(0), Value::Integer(500000500000)]
+    );
+}
+
+#[test]
+fn non_tail_recursion_overflows() {
+    let source = COUNTDOWN.replace("{ .n 1 - countdown }", "{ .n 1 - countdown 0 + }");
+    let Err(err) = run(&source, 1000) else {
+        panic!("expected call stack overflow");
+    };
+    assert_eq!(err.kind, RuntimeErrorKind::CallStackOverflow);
+    assert_eq!(err.backtrace.len(), 1000);
+}
@@ -0,0 +1,49 @@
+use std::rc::Rc;
+
+use celo::compiler::{
+    source::{MemoryFileProvider, Source},
+    Compiler,
+};
+use maquina::vm::{error::RuntimeErrorKind, Vm};
+
+const CODE: &str = concat!(
+    "fn! square { dup * }\n",
+    "test! squares { 3 square 9 = assert }\n",
+    "test! fails { 2 square 5 = assert }\n",
+);
+
+fn compiler(code: &str) -> Compiler {
+    let source = Source::from_string("test.celo", code);
+    Compiler::with_file_provider(source, Rc::new(MemoryFileProvider::new()))
+}
+
+#[test]
+fn runs_tests_on_fresh_vms() {
+    let (program, tests) = compiler(CODE).compile_tests().expect("compile tests");
+    let names: Vec<&str> = tests.iter().map(|test| &*test.name).collect();
+    assert_eq!(names, ["squares", "fails"]);
+
+    let mut vm = Vm::new(program.clone());
+    vm.call(tests[0].function as u32).expect("passing test");
+    let mut vm = Vm::new(program);
+    let err = vm.call(tests[1].function as u32).unwrap_err();
+    assert_eq!(err.kind, RuntimeErrorKind::AssertionFailed);
+}
+
+#[test]
+fn tests_are_not_compiled_into_programs() {
+    let code = format!("{CODE}fn! main {{ 2 square print }}\n");
+    let program = compiler(&code).compile().expect("compile program");
+    assert_eq!(program.functions.len(), 2);
+}
+
+#[test]
+fn rejects_duplicate_tests() {
+    let err = compiler("test! a { }\ntest! a { }")
+        .compile_tests()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "test.celo:2:7: test is already defined at 1:7"
+    );
+}
@@ -0,0 +1,135 @@
+use celo::compiler::{
+    error::{Error, Result},
+    hir,
+    lexer::{Lexer, LexerErrorKind},
+    parser::ParseHirStep,
+    source::{Location, Source, TokenKind},
+    Compiler,
+};
+
+fn texts(source: &Source, lexer: Lexer) -> Vec<&str> {
+    lexer
+        .map(|token| &source[token.unwrap().location])
+        .collect()
+}
+
+#[test]
+fn iterates_over_tokens() {
+    let source = Source::from_string("test.celo", "fn! main { 1 -> .x }");
+    let lexer = Lexer::new(source.clone());
+    assert_eq!(
+        texts(&source, lexer),
+        ["fn!", "main", "{", "1", "->", ".x", "}"]
+    );
+
+    let source = Source::from_string("test.celo", "1 $ 2");
+    let mut lexer = Lexer::new(source.clone());
+    assert!(matches!(lexer.next(), Some(Ok(_))));
+    let Some(Err(Error::Lexer(err))) = lexer.next() else {
+        panic!("`$` should not lex");
+    };
+    assert!(matches!(err.kind(), LexerErrorKind::InvalidCharacter));
+    let rest: Vec<_> = lexer.map(|token| token.unwrap().kind).collect();
+    assert_eq!(rest, [TokenKind::Integer]);
+}
+
+#[test]
+fn peeks_arbitrarily_far() {
+    let source = Source::from_string("test.celo", ";; Doc\nfn! main { }");
+    let mut lexer = Lexer::new(source.clone());
+    let third = lexer.peek_nth(2).unwrap().unwrap();
+    assert_eq!(third.kind, TokenKind::LeftCurly);
+    assert!(lexer.peek_nth(4).unwrap().is_none());
+    assert_eq!(lexer.doc_comment().as_deref(), Some("Doc"));
+
+    let first = lexer.peek_token().unwrap().unwrap();
+    assert_eq!(&source[first.location], "fn!");
+    lexer.consume_token().unwrap();
+    assert_eq!(lexer.doc_comment(), None);
+    assert_eq!(texts(&source, lexer), ["main", "{", "}"]);
+}
+
+#[test]
+fn rewinds_to_checkpoints() {
+    let source = Source::from_string("test.celo", "1 ;; Doc\n.x 2 3");
+    let mut lexer = Lexer::new(source.clone());
+    lexer.consume_token().unwrap();
+    lexer.peek_nth(1).unwrap();
+    let checkpoint = lexer.checkpoint();
+    lexer.consume_token().unwrap();
+    lexer.consume_token().unwrap();
+    lexer.consume_token().unwrap();
+    assert!(lexer.peek_token().unwrap().is_none());
+
+    lexer.rewind(checkpoint);
+    assert_eq!(lexer.doc_comment().as_deref(), Some("Doc"));
+    lexer.consume_token().unwrap();
+    assert_eq!(texts(&source, lexer), ["2", "3"]);
+}
+
+/// `def!` is `fn!` with an optional list of parameter names in parentheses. Any other group in
+/// that place is the first code of the body instead.
+fn macro_def(step: &mut ParseHirStep) -> Result<()> {
+    let name = step.expect_token(TokenKind::Identifier)?.location;
+    let checkpoint = step.lexer.checkpoint();
+    let (parameters, prelude) = match parse_parameters(step) {
+        Ok(parameters) => (parameters, Vec::new()),
+        Err(_) => {
+            step.lexer.rewind(checkpoint);
+            let prelude = match step.lexer.peek_token()? {
+                Some(token) if token.kind == TokenKind::LeftParen => step.parse_group()?.nodes,
+                _ => Vec::new(),
+            };
+            (Vec::new(), prelude)
+        }
+    };
+    let mut scope = step.parse_scope()?;
+    scope.code.splice(0..0, prelude);
+    let mut function = hir::Function::new(name.span_to(scope.end), name, scope);
+    function.doc = Some(format!("{} parameters", parameters.len()));
+    step.add_function(function);
+    Ok(())
+}
+
+fn parse_parameters(step: &mut ParseHirStep) -> Result<Vec<Location>> {
+    step.expect_token(TokenKind::LeftParen)?;
+    let mut parameters = Vec::new();
+    while let Some(token) = step.lexer.peek_token()? {
+        if token.kind != TokenKind::DotIdentifier {
+            break;
+        }
+        step.lexer.consume_token()?;
+        parameters.push(token.location);
+    }
+    step.expect_token(TokenKind::RightParen)?;
+    Ok(parameters)
+}
+
+#[test]
+fn macros_can_backtrack() {
+    let code = "def! add (.a .b) { .a .b + }\ndef! three (.a 2 +) { 1 + }\ndef! none { }";
+    let source = Source::from_string("test.celo", code);
+    let compiler = Compiler::new(source.clone());
+    let mut step = ParseHirStep::new(&compiler, source.clone());
+    step.add_root_macro("def", macro_def);
+    let hir = step.run().unwrap();
+    let functions: Vec<_> = hir.modules[0]
+        .functions
+        .iter()
+        .map(|function| {
+            (
+                &source[function.name],
+                function.doc.as_deref().unwrap(),
+                function.body.code.len(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        functions,
+        [
+            ("add", "2 parameters", 3),
+            ("three", "0 parameters", 5),
+            ("none", "0 parameters", 0),
+        ]
+    );
+}
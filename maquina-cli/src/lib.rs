@@ -1,4 +1,83 @@
-pub fn main(_args: &[String]) -> i32 {
-    eprintln!("The maquina CLI is not implemented yet.");
-    1
+use std::{fs::File, io::BufReader};
+
+use maquina::vm::{bytecode, heap::Heap, Config, Vm};
+
+const USAGE: &str =
+    "usage: maq run [--gc-stats] [--gc-threshold <bytes>] [--heap-limit <bytes>] <file>";
+
+pub fn main(args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        Some("run") => run(&args[1..]),
+        _ => {
+            eprintln!("{USAGE}");
+            1
+        }
+    }
+}
+
+fn run(args: &[String]) -> i32 {
+    let mut config = Config::default();
+    let mut gc_stats = false;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gc-stats" => gc_stats = true,
+            "--gc-threshold" => {
+                let Some(bytes) = args.next().and_then(|bytes| bytes.parse().ok()) else {
+                    eprintln!("{USAGE}");
+                    return 1;
+                };
+                config.heap.gc_threshold = bytes;
+            }
+            "--heap-limit" => {
+                let Some(bytes) = args.next().and_then(|bytes| bytes.parse().ok()) else {
+                    eprintln!("{USAGE}");
+                    return 1;
+                };
+                config.heap.limit = Some(bytes);
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return 1;
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{USAGE}");
+        return 1;
+    };
+    let program =
+        match File::open(path).and_then(|file| bytecode::read_program(&mut BufReader::new(file))) {
+            Ok(program) => program,
+            Err(err) => {
+                eprintln!("error: could not load {path}: {err}");
+                return 1;
+            }
+        };
+    let mut vm = Vm::with_config(program, config);
+    let result = vm.run();
+    if gc_stats {
+        print_gc_stats(vm.heap());
+    }
+    if let Err(err) = result {
+        eprintln!("{err}");
+        return 1;
+    }
+    0
+}
+
+fn print_gc_stats(heap: &Heap) {
+    let stats = heap.stats();
+    eprintln!("gc collections:    {}", stats.collections);
+    eprintln!("gc time:           {:?}", stats.time);
+    eprintln!("allocated objects: {}", stats.allocated_objects);
+    eprintln!("freed objects:     {}", stats.freed_objects);
+    eprintln!("freed bytes:       {}", stats.freed_bytes);
+    eprintln!("live objects:      {}", stats.live_objects);
+    eprintln!("live bytes:        {}", stats.live_bytes);
+    eprintln!("peak bytes:        {}", stats.peak_bytes);
+    eprintln!("heap objects:      {}", heap.len());
+    eprintln!("heap bytes:        {}", heap.bytes());
 }
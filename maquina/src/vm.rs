@@ -0,0 +1,310 @@
+use std::io::{self, Write};
+
+use self::{
+    error::{Result, RuntimeError, RuntimeErrorKind, SourcePosition, TraceFrame},
+    heap::{Heap, HeapConfig},
+    program::{Instruction, Program},
+    value::Value,
+};
+
+pub mod bytecode;
+pub mod error;
+pub mod heap;
+pub mod intrinsic;
+pub mod program;
+pub mod value;
+
+pub struct Vm {
+    program: Program,
+    config: Config,
+    heap: Heap,
+    stack: Vec<Value>,
+    locals: Vec<Value>,
+    globals: Vec<Value>,
+    frames: Vec<Frame>,
+    handlers: Vec<Handler>,
+    /// Destination of `print`
+    output: Box<dyn Write>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub heap: HeapConfig,
+    /// Maximum number of nested calls.
+    pub max_frames: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            heap: HeapConfig::default(),
+            max_frames: 1 << 16,
+        }
+    }
+}
+
+struct Frame {
+    function: u32,
+    pc: u32,
+    /// Index of the first local of this frame
+    locals: u32,
+}
+
+/// Error handler installed by a `try` instruction.
+struct Handler {
+    /// Index of the frame that installed the handler
+    frame: u32,
+    /// Stack height when the handler was installed
+    stack: u32,
+    pc: u32,
+}
+
+impl Vm {
+    pub fn new(program: Program) -> Self {
+        Self::with_config(program, Config::default())
+    }
+
+    pub fn with_config(program: Program, config: Config) -> Self {
+        Self {
+            globals: vec![Value::default(); program.globals as usize],
+            program,
+            config,
+            heap: Heap::new(config.heap),
+            stack: Vec::new(),
+            locals: Vec::new(),
+            frames: Vec::new(),
+            handlers: Vec::new(),
+            output: Box::new(io::stdout()),
+        }
+    }
+
+    /// Redirects the output of `print`, which goes to stdout by default.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Box::new(output);
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn program_mut(&mut self) -> &mut Program {
+        &mut self.program
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    pub fn globals(&self) -> &[Value] {
+        &self.globals
+    }
+
+    /// Runs a garbage collection with the stack, locals and globals as roots.
+    pub fn collect_garbage(&mut self) -> Result<()> {
+        let roots = self.stack.iter().chain(&self.locals).chain(&self.globals);
+        self.heap.collect(roots.copied());
+        if self.heap.is_over_limit() {
+            return Err(RuntimeErrorKind::HeapLimitExceeded.into());
+        }
+        Ok(())
+    }
+
+    /// Calls the entry function of the program.
+    pub fn run(&mut self) -> Result<()> {
+        let Some(entry) = self.program.entry else {
+            return Err(RuntimeErrorKind::MissingEntry.into());
+        };
+        self.call(entry)
+    }
+
+    /// Calls a function and runs until it returns.
+    ///
+    /// If the call fails, its frames are discarded and the error carries a backtrace.
+    pub fn call(&mut self, function: u32) -> Result<()> {
+        // The program may have been extended since the last call
+        self.globals
+            .resize(self.program.globals as usize, Value::default());
+        let depth = self.frames.len();
+        let locals = self.locals.len();
+        let result = self.push_frame(function).and_then(|()| self.execute(depth));
+        if let Err(mut err) = result {
+            self.trace(&mut err);
+            self.frames.truncate(depth);
+            self.locals.truncate(locals);
+            self.handlers
+                .retain(|handler| (handler.frame as usize) < depth);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Runs until the frame count drops to `depth`.
+    fn execute(&mut self, depth: usize) -> Result<()> {
+        while self.frames.len() > depth {
+            if let Err(err) = self.step() {
+                self.catch(err, depth)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Unwinds to the innermost handler installed above `depth` and passes it the error.
+    ///
+    /// Thrown values are passed as they are, other errors as their message. Exceeding the heap
+    /// limit cannot be caught.
+    fn catch(&mut self, err: RuntimeError, depth: usize) -> Result<()> {
+        if err.kind == RuntimeErrorKind::HeapLimitExceeded {
+            return Err(err);
+        }
+        let Some(handler) = self
+            .handlers
+            .pop_if(|handler| handler.frame as usize >= depth)
+        else {
+            return Err(err);
+        };
+        self.frames.truncate(handler.frame as usize + 1);
+        let frame = self.frames.last_mut().expect("call frame");
+        frame.pc = handler.pc;
+        let count = self.program.functions[frame.function as usize].locals;
+        self.locals.truncate((frame.locals + count) as usize);
+        self.stack.truncate(handler.stack as usize);
+        let value = match err.kind {
+            RuntimeErrorKind::Thrown { value, .. } => value,
+            kind => self.heap.alloc_string(kind.to_string()),
+        };
+        self.push(value);
+        Ok(())
+    }
+
+    /// Removes the handlers installed by the current frame.
+    fn pop_handlers(&mut self) {
+        let frame = self.frames.len() as u32 - 1;
+        while self
+            .handlers
+            .pop_if(|handler| handler.frame == frame)
+            .is_some()
+        {}
+    }
+
+    /// Adds the failing instruction and the backtrace of the current frames to an error.
+    fn trace(&self, err: &mut RuntimeError) {
+        for (i, frame) in self.frames.iter().rev().enumerate() {
+            let function = &self.program.functions[frame.function as usize];
+            let pc = frame.pc.saturating_sub(1) as usize;
+            if i == 0 {
+                err.instruction = function.code.get(pc).cloned();
+            }
+            let position = function.debug.as_ref().and_then(|debug| {
+                let &(line, column) = debug.positions.get(pc)?;
+                Some(SourcePosition {
+                    path: debug.path.clone(),
+                    line,
+                    column,
+                })
+            });
+            err.backtrace.push(TraceFrame {
+                function: function.name.clone(),
+                position,
+            });
+        }
+    }
+
+    fn push_frame(&mut self, function: u32) -> Result<()> {
+        if self.frames.len() >= self.config.max_frames {
+            return Err(RuntimeErrorKind::CallStackOverflow.into());
+        }
+        let locals = self.locals.len();
+        let count = self.program.functions[function as usize].locals as usize;
+        self.locals.resize(locals + count, Value::default());
+        self.frames.push(Frame {
+            function,
+            pc: 0,
+            locals: locals as u32,
+        });
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<()> {
+        // Collect before advancing so that errors point at the instruction that allocated
+        if self.heap.should_collect() {
+            self.collect_garbage()?;
+        }
+        let frame = self.frames.last_mut().expect("call frame");
+        let instruction =
+            self.program.functions[frame.function as usize].code[frame.pc as usize].clone();
+        frame.pc += 1;
+        let locals = frame.locals as usize;
+        match instruction {
+            Instruction::Integer(integer) => self.push(Value::Integer(integer)),
+            Instruction::Float(float) => self.push(Value::Float(float)),
+            Instruction::String(string) => {
+                let string = self.heap.alloc_string(string);
+                self.push(string);
+            }
+            Instruction::Load(local) => self.push(self.locals[locals + local as usize]),
+            Instruction::Store(local) => {
+                let value = self.pop()?;
+                self.locals[locals + local as usize] = value;
+            }
+            Instruction::LoadGlobal(global) => self.push(self.globals[global as usize]),
+            Instruction::StoreGlobal(global) => {
+                let value = self.pop()?;
+                self.globals[global as usize] = value;
+            }
+            Instruction::Call(function) => self.push_frame(function)?,
+            Instruction::TailCall(function) => {
+                self.pop_handlers();
+                let count = self.program.functions[function as usize].locals as usize;
+                self.locals.truncate(locals);
+                self.locals.resize(locals + count, Value::default());
+                let frame = self.frames.last_mut().expect("call frame");
+                frame.function = function;
+                frame.pc = 0;
+            }
+            Instruction::Return => {
+                self.pop_handlers();
+                self.frames.pop();
+                self.locals.truncate(locals);
+            }
+            Instruction::Jump(target) => self.frames.last_mut().expect("call frame").pc = target,
+            Instruction::JumpIfZero(target) => {
+                if self.pop_integer()? == 0 {
+                    self.frames.last_mut().expect("call frame").pc = target;
+                }
+            }
+            Instruction::Try(pc) => self.handlers.push(Handler {
+                frame: self.frames.len() as u32 - 1,
+                stack: self.stack.len() as u32,
+                pc,
+            }),
+            Instruction::EndTry => _ = self.handlers.pop(),
+            Instruction::Intrinsic(intrinsic) => self.intrinsic(intrinsic)?,
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Result<Value> {
+        self.stack
+            .pop()
+            .ok_or_else(|| RuntimeErrorKind::StackUnderflow.into())
+    }
+
+    fn pop_integer(&mut self) -> Result<i64> {
+        match self.pop()? {
+            Value::Integer(integer) => Ok(integer),
+            value => Err(RuntimeErrorKind::TypeMismatch {
+                expected: "integer",
+                got: self.heap.type_name(value),
+            }
+            .into()),
+        }
+    }
+}
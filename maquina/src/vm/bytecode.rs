@@ -0,0 +1,251 @@
+//! Binary encoding of [Program]s as written by `celo build` and read by `maq run`.
+
+use std::{
+    io::{self, Read, Write},
+    rc::Rc,
+};
+
+use super::{
+    intrinsic::Intrinsic,
+    program::{DebugInfo, Function, Instruction, Program},
+};
+
+pub const MAGIC: &[u8; 4] = b"MAQ\x02";
+
+const NO_ENTRY: u32 = u32::MAX;
+
+pub fn write_program(out: &mut impl Write, program: &Program) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    write_u32(out, program.entry.unwrap_or(NO_ENTRY))?;
+    write_u32(out, program.globals)?;
+    write_u32(out, program.functions.len() as u32)?;
+    for function in &program.functions {
+        write_str(out, &function.name)?;
+        write_u32(out, function.locals)?;
+        write_u32(out, function.code.len() as u32)?;
+        for instruction in &function.code {
+            write_instruction(out, instruction)?;
+        }
+        let Some(debug) = &function.debug else {
+            write_u8(out, 0)?;
+            continue;
+        };
+        write_u8(out, 1)?;
+        write_str(out, &debug.path)?;
+        for &(line, column) in &debug.positions {
+            write_u32(out, line)?;
+            write_u32(out, column)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_program(input: &mut impl Read) -> io::Result<Program> {
+    let mut magic = [0; 4];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a maquina program"));
+    }
+    let entry = match read_u32(input)? {
+        NO_ENTRY => None,
+        entry => Some(entry),
+    };
+    let globals = read_u32(input)?;
+    let function_count = read_u32(input)?;
+    let mut functions = Vec::new();
+    for _ in 0..function_count {
+        let name = read_str(input)?;
+        let locals = read_u32(input)?;
+        let code_len = read_u32(input)?;
+        let code = (0..code_len)
+            .map(|_| read_instruction(input))
+            .collect::<io::Result<_>>()?;
+        let debug = match read_u8(input)? {
+            0 => None,
+            _ => {
+                let path = read_str(input)?;
+                let positions = (0..code_len)
+                    .map(|_| Ok((read_u32(input)?, read_u32(input)?)))
+                    .collect::<io::Result<_>>()?;
+                Some(DebugInfo { path, positions })
+            }
+        };
+        functions.push(Function {
+            name,
+            locals,
+            code,
+            debug,
+        });
+    }
+    if entry.is_some_and(|entry| entry >= function_count) {
+        return Err(invalid_data("invalid entry function"));
+    }
+    for function in &functions {
+        validate_function(function, function_count, globals)?;
+    }
+    Ok(Program {
+        functions,
+        globals,
+        entry,
+    })
+}
+
+/// Checks that a function only refers to existing functions, locals, globals and instructions
+/// and cannot run past its end.
+fn validate_function(function: &Function, functions: u32, globals: u32) -> io::Result<()> {
+    let code_len = function.code.len() as u32;
+    for instruction in &function.code {
+        let valid = match *instruction {
+            Instruction::Load(local) | Instruction::Store(local) => local < function.locals,
+            Instruction::LoadGlobal(global) | Instruction::StoreGlobal(global) => global < globals,
+            Instruction::Call(callee) | Instruction::TailCall(callee) => callee < functions,
+            Instruction::Jump(target)
+            | Instruction::JumpIfZero(target)
+            | Instruction::Try(target) => target < code_len,
+            _ => true,
+        };
+        if !valid {
+            return Err(invalid_data(&format!(
+                "invalid operand in function `{}`",
+                function.name
+            )));
+        }
+    }
+    if !matches!(function.code.last(), Some(Instruction::Return)) {
+        return Err(invalid_data(&format!(
+            "function `{}` does not end with a return",
+            function.name
+        )));
+    }
+    Ok(())
+}
+
+fn write_instruction(out: &mut impl Write, instruction: &Instruction) -> io::Result<()> {
+    match instruction {
+        Instruction::Integer(integer) => {
+            write_u8(out, 0)?;
+            out.write_all(&integer.to_le_bytes())
+        }
+        Instruction::Float(float) => {
+            write_u8(out, 1)?;
+            out.write_all(&float.to_le_bytes())
+        }
+        Instruction::String(string) => {
+            write_u8(out, 2)?;
+            write_str(out, string)
+        }
+        Instruction::Load(local) => {
+            write_u8(out, 3)?;
+            write_u32(out, *local)
+        }
+        Instruction::Store(local) => {
+            write_u8(out, 4)?;
+            write_u32(out, *local)
+        }
+        Instruction::LoadGlobal(global) => {
+            write_u8(out, 5)?;
+            write_u32(out, *global)
+        }
+        Instruction::StoreGlobal(global) => {
+            write_u8(out, 6)?;
+            write_u32(out, *global)
+        }
+        Instruction::Call(function) => {
+            write_u8(out, 7)?;
+            write_u32(out, *function)
+        }
+        Instruction::Return => write_u8(out, 8),
+        Instruction::Intrinsic(intrinsic) => {
+            write_u8(out, 9)?;
+            write_u8(out, *intrinsic as u8)
+        }
+        Instruction::TailCall(function) => {
+            write_u8(out, 10)?;
+            write_u32(out, *function)
+        }
+        Instruction::Jump(target) => {
+            write_u8(out, 11)?;
+            write_u32(out, *target)
+        }
+        Instruction::JumpIfZero(target) => {
+            write_u8(out, 12)?;
+            write_u32(out, *target)
+        }
+        Instruction::Try(handler) => {
+            write_u8(out, 13)?;
+            write_u32(out, *handler)
+        }
+        Instruction::EndTry => write_u8(out, 14),
+    }
+}
+
+fn read_instruction(input: &mut impl Read) -> io::Result<Instruction> {
+    Ok(match read_u8(input)? {
+        0 => Instruction::Integer(i64::from_le_bytes(read_array(input)?)),
+        1 => Instruction::Float(f64::from_le_bytes(read_array(input)?)),
+        2 => Instruction::String(read_str(input)?),
+        3 => Instruction::Load(read_u32(input)?),
+        4 => Instruction::Store(read_u32(input)?),
+        5 => Instruction::LoadGlobal(read_u32(input)?),
+        6 => Instruction::StoreGlobal(read_u32(input)?),
+        7 => Instruction::Call(read_u32(input)?),
+        8 => Instruction::Return,
+        9 => {
+            let index = read_u8(input)? as usize;
+            let Some(&intrinsic) = Intrinsic::ALL.get(index) else {
+                return Err(invalid_data("invalid intrinsic"));
+            };
+            Instruction::Intrinsic(intrinsic)
+        }
+        10 => Instruction::TailCall(read_u32(input)?),
+        11 => Instruction::Jump(read_u32(input)?),
+        12 => Instruction::JumpIfZero(read_u32(input)?),
+        13 => Instruction::Try(read_u32(input)?),
+        14 => Instruction::EndTry,
+        _ => return Err(invalid_data("invalid opcode")),
+    })
+}
+
+fn write_u8(out: &mut impl Write, value: u8) -> io::Result<()> {
+    out.write_all(&[value])
+}
+
+fn write_u32(out: &mut impl Write, value: u32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn write_str(out: &mut impl Write, string: &str) -> io::Result<()> {
+    write_u32(out, string.len() as u32)?;
+    out.write_all(string.as_bytes())
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    Ok(read_array::<1>(input)?[0])
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_array(input)?))
+}
+
+fn read_str(input: &mut impl Read) -> io::Result<Rc<str>> {
+    let len = read_u32(input)? as usize;
+    let mut bytes = Vec::new();
+    input.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    match String::from_utf8(bytes) {
+        Ok(string) => Ok(string.into()),
+        Err(_) => Err(invalid_data("invalid utf-8 string")),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
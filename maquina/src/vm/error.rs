@@ -0,0 +1,111 @@
+use std::{fmt, rc::Rc};
+
+use super::{program::Instruction, value::Value};
+
+pub type Result<T> = std::result::Result<T, RuntimeError>;
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    /// The instruction that failed
+    pub instruction: Option<Instruction>,
+    /// The active calls, innermost first
+    pub backtrace: Vec<TraceFrame>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeErrorKind {
+    StackUnderflow,
+    CallStackOverflow,
+    DivisionByZero,
+    TypeMismatch {
+        expected: &'static str,
+        got: &'static str,
+    },
+    IndexOutOfBounds {
+        index: i64,
+        len: usize,
+    },
+    KeyNotFound,
+    /// `pop` on an array without elements
+    EmptyArray,
+    HeapLimitExceeded,
+    MissingEntry,
+    Io(String),
+    /// `assert` popped zero
+    AssertionFailed,
+    /// Value raised by `throw`, along with its textual form
+    Thrown {
+        value: Value,
+        message: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct TraceFrame {
+    pub function: Rc<str>,
+    /// Source position of the active instruction, if the function has debug info
+    pub position: Option<SourcePosition>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SourcePosition {
+    pub path: Rc<str>,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl From<RuntimeErrorKind> for RuntimeError {
+    fn from(kind: RuntimeErrorKind) -> Self {
+        Self {
+            kind,
+            instruction: None,
+            backtrace: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            RuntimeErrorKind::CallStackOverflow => write!(f, "call stack overflow"),
+            RuntimeErrorKind::DivisionByZero => write!(f, "division by zero"),
+            RuntimeErrorKind::TypeMismatch { expected, got } => {
+                write!(f, "type mismatch: expected {expected}, got {got}")
+            }
+            RuntimeErrorKind::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} out of bounds for length {len}")
+            }
+            RuntimeErrorKind::KeyNotFound => write!(f, "key not found"),
+            RuntimeErrorKind::EmptyArray => write!(f, "pop from empty array"),
+            RuntimeErrorKind::HeapLimitExceeded => write!(f, "heap limit exceeded"),
+            RuntimeErrorKind::MissingEntry => write!(f, "program has no entry function"),
+            RuntimeErrorKind::Io(message) => write!(f, "i/o error: {message}"),
+            RuntimeErrorKind::AssertionFailed => write!(f, "assertion failed"),
+            RuntimeErrorKind::Thrown { message, .. } => write!(f, "uncaught exception: {message}"),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "runtime error: {}", self.kind)?;
+        if let Some(instruction) = &self.instruction {
+            write!(f, "\n  in instruction {instruction:?}")?;
+        }
+        for frame in &self.backtrace {
+            write!(f, "\n  at {}", frame.function)?;
+            if let Some(position) = &frame.position {
+                write!(
+                    f,
+                    " ({}:{}:{})",
+                    position.path, position.line, position.column
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
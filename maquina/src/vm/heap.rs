@@ -0,0 +1,342 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    mem::size_of,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use super::value::Value;
+
+/// Handle to an object allocated on the [Heap].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectRef(u32);
+
+#[derive(Debug)]
+pub enum Object {
+    /// Immutable string.
+    String(Rc<str>),
+    /// Growable array.
+    Array(Vec<Value>),
+    /// Hash map from integer or string keys to values.
+    Map(HashMap<Key, Value>),
+}
+
+impl Object {
+    /// Estimates the number of bytes used by the object.
+    pub fn size(&self) -> usize {
+        size_of::<Slot>()
+            + match self {
+                Object::String(string) => string.len(),
+                Object::Array(elements) => elements.capacity() * size_of::<Value>(),
+                Object::Map(entries) => entries.capacity() * size_of::<(Key, Value)>(),
+            }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::String(_) => "string",
+            Object::Array(_) => "array",
+            Object::Map(_) => "map",
+        }
+    }
+}
+
+/// Hashable copy of a value used to index maps.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Key {
+    Integer(i64),
+    String(Rc<str>),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HeapConfig {
+    /// Number of allocated bytes that triggers the first collection.
+    pub gc_threshold: usize,
+    /// Maximum number of bytes that may stay alive after a collection.
+    pub limit: Option<usize>,
+}
+
+impl Default for HeapConfig {
+    fn default() -> Self {
+        Self {
+            gc_threshold: 1 << 20,
+            limit: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GcStats {
+    pub collections: usize,
+    pub allocated_objects: usize,
+    pub freed_objects: usize,
+    pub freed_bytes: usize,
+    /// Number of objects that survived the last collection
+    pub live_objects: usize,
+    /// Number of bytes that survived the last collection
+    pub live_bytes: usize,
+    pub peak_bytes: usize,
+    pub time: Duration,
+}
+
+#[derive(Debug)]
+struct Slot {
+    object: Object,
+    marked: bool,
+}
+
+/// Garbage collected object heap.
+///
+/// Objects are reclaimed by a mark-and-sweep collection which is started by the VM once the
+/// estimated heap size exceeds the collection threshold.
+#[derive(Debug, Default)]
+pub struct Heap {
+    config: HeapConfig,
+    slots: Vec<Option<Slot>>,
+    free: Vec<u32>,
+    /// Estimated size of all objects, including unreachable ones
+    bytes: usize,
+    next_collection: usize,
+    stats: GcStats,
+}
+
+impl Heap {
+    pub fn new(config: HeapConfig) -> Self {
+        Self {
+            config,
+            next_collection: config.gc_threshold,
+            ..Default::default()
+        }
+    }
+
+    pub fn alloc(&mut self, object: Object) -> ObjectRef {
+        self.grow(object.size());
+        self.stats.allocated_objects += 1;
+        let slot = Some(Slot {
+            object,
+            marked: false,
+        });
+        if let Some(index) = self.free.pop() {
+            self.slots[index as usize] = slot;
+            return ObjectRef(index);
+        }
+        let index = self.slots.len() as u32;
+        self.slots.push(slot);
+        ObjectRef(index)
+    }
+
+    pub fn get(&self, object: ObjectRef) -> &Object {
+        &self.slots[object.0 as usize]
+            .as_ref()
+            .expect("live object")
+            .object
+    }
+
+    pub fn get_mut(&mut self, object: ObjectRef) -> &mut Object {
+        &mut self.slots[object.0 as usize]
+            .as_mut()
+            .expect("live object")
+            .object
+    }
+
+    /// Accounts for an object that grew by the given number of bytes.
+    pub fn grow(&mut self, bytes: usize) {
+        self.bytes += bytes;
+        self.stats.peak_bytes = self.stats.peak_bytes.max(self.bytes);
+    }
+
+    /// Returns the number of objects on the heap, including unreachable ones.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the estimated size of the heap in bytes, including unreachable objects.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn stats(&self) -> &GcStats {
+        &self.stats
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.bytes >= self.next_collection
+    }
+
+    /// Returns whether more bytes than allowed stayed alive after the last collection.
+    pub fn is_over_limit(&self) -> bool {
+        self.config
+            .limit
+            .is_some_and(|limit| self.stats.live_bytes > limit)
+    }
+
+    /// Frees all objects that are not reachable from the given roots.
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = Value>) {
+        let start = Instant::now();
+        let objects = self.len();
+        let mut worklist: Vec<ObjectRef> = roots
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::Object(object) => Some(object),
+                _ => None,
+            })
+            .collect();
+        while let Some(object) = worklist.pop() {
+            let slot = self.slots[object.0 as usize].as_mut().expect("live object");
+            if slot.marked {
+                continue;
+            }
+            slot.marked = true;
+            let children: Box<dyn Iterator<Item = &Value>> = match &slot.object {
+                Object::String(_) => continue,
+                Object::Array(elements) => Box::new(elements.iter()),
+                Object::Map(entries) => Box::new(entries.values()),
+            };
+            worklist.extend(children.filter_map(|value| match value {
+                Value::Object(object) => Some(*object),
+                _ => None,
+            }));
+        }
+        let mut live_objects = 0;
+        let mut live_bytes = 0;
+        for (index, entry) in self.slots.iter_mut().enumerate() {
+            let Some(slot) = entry else {
+                continue;
+            };
+            if slot.marked {
+                slot.marked = false;
+                live_objects += 1;
+                live_bytes += slot.object.size();
+                continue;
+            }
+            *entry = None;
+            self.free.push(index as u32);
+        }
+        self.stats.collections += 1;
+        self.stats.freed_objects += objects - live_objects;
+        self.stats.freed_bytes += self.bytes.saturating_sub(live_bytes);
+        self.stats.live_objects = live_objects;
+        self.stats.live_bytes = live_bytes;
+        self.stats.time += start.elapsed();
+        self.bytes = live_bytes;
+        self.next_collection = (live_bytes * 2).max(self.config.gc_threshold);
+    }
+
+    pub fn alloc_string(&mut self, string: impl Into<Rc<str>>) -> Value {
+        Value::Object(self.alloc(Object::String(string.into())))
+    }
+
+    /// Converts a value into a map key.
+    pub fn key(&self, value: Value) -> Option<Key> {
+        match value {
+            Value::Integer(integer) => Some(Key::Integer(integer)),
+            Value::Object(object) => match self.get(object) {
+                Object::String(string) => Some(Key::String(string.clone())),
+                _ => None,
+            },
+            Value::Float(_) => None,
+        }
+    }
+
+    pub fn type_name(&self, value: Value) -> &'static str {
+        match value {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Object(object) => self.get(object).type_name(),
+        }
+    }
+
+    /// Compares two values, strings by content and other objects by identity.
+    pub fn equals(&self, a: Value, b: Value) -> bool {
+        match (a, b) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Integer(a), Value::Float(b)) | (Value::Float(b), Value::Integer(a)) => {
+                a as f64 == b
+            }
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Object(a), Value::Object(b)) => match (self.get(a), self.get(b)) {
+                (Object::String(a), Object::String(b)) => a == b,
+                _ => a == b,
+            },
+            _ => false,
+        }
+    }
+
+    /// Formats a value the way `print` shows it.
+    pub fn display(&self, value: Value) -> String {
+        let mut out = String::new();
+        self.display_into(&mut out, value, &mut Vec::new(), false);
+        out
+    }
+
+    /// Formats a value the way it is shown inside of collections, with strings quoted.
+    pub fn inspect(&self, value: Value) -> String {
+        let mut out = String::new();
+        self.display_into(&mut out, value, &mut Vec::new(), true);
+        out
+    }
+
+    fn display_into(
+        &self,
+        out: &mut String,
+        value: Value,
+        seen: &mut Vec<ObjectRef>,
+        nested: bool,
+    ) {
+        let object = match value {
+            Value::Integer(integer) => {
+                _ = write!(out, "{integer}");
+                return;
+            }
+            Value::Float(float) => {
+                _ = write!(out, "{float:?}");
+                return;
+            }
+            Value::Object(object) => object,
+        };
+        if seen.contains(&object) {
+            out.push_str("...");
+            return;
+        }
+        match self.get(object) {
+            Object::String(string) if nested => _ = write!(out, "{string:?}"),
+            Object::String(string) => out.push_str(string),
+            Object::Array(array) => {
+                seen.push(object);
+                out.push('[');
+                for (i, &element) in array.iter().enumerate() {
+                    if i > 0 {
+                        out.push(' ');
+                    }
+                    self.display_into(out, element, seen, true);
+                }
+                out.push(']');
+                seen.pop();
+            }
+            Object::Map(map) => {
+                seen.push(object);
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                out.push('{');
+                for (i, (key, &value)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(' ');
+                    }
+                    match key {
+                        Key::Integer(integer) => _ = write!(out, "{integer}"),
+                        Key::String(string) => _ = write!(out, "{string:?}"),
+                    }
+                    out.push_str(": ");
+                    self.display_into(out, value, seen, true);
+                }
+                out.push('}');
+                seen.pop();
+            }
+        }
+    }
+}
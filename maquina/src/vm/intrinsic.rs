@@ -0,0 +1,400 @@
+use std::{collections::HashMap, fs, io::Write, mem::size_of};
+
+use super::{
+    error::{Result, RuntimeError, RuntimeErrorKind},
+    heap::{Key, Object},
+    value::Value,
+    Vm,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Intrinsic {
+    // Stack
+    Dup,
+    Drop,
+    Swap,
+    Over,
+    Rot,
+    // Arithmetic
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    // Comparison
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Not,
+    // Strings
+    ToString,
+    Concat,
+    Print,
+    // Collections
+    Array,
+    Map,
+    Push,
+    Pop,
+    Get,
+    Set,
+    Has,
+    Remove,
+    Len,
+    // Errors
+    Throw,
+    // Files
+    ReadFile,
+    WriteFile,
+    // Testing
+    Assert,
+}
+
+impl Intrinsic {
+    /// All intrinsics ordered by their discriminant.
+    pub const ALL: [Intrinsic; 32] = [
+        Intrinsic::Dup,
+        Intrinsic::Drop,
+        Intrinsic::Swap,
+        Intrinsic::Over,
+        Intrinsic::Rot,
+        Intrinsic::Add,
+        Intrinsic::Sub,
+        Intrinsic::Mul,
+        Intrinsic::Div,
+        Intrinsic::Rem,
+        Intrinsic::Eq,
+        Intrinsic::Lt,
+        Intrinsic::Le,
+        Intrinsic::Gt,
+        Intrinsic::Ge,
+        Intrinsic::Not,
+        Intrinsic::ToString,
+        Intrinsic::Concat,
+        Intrinsic::Print,
+        Intrinsic::Array,
+        Intrinsic::Map,
+        Intrinsic::Push,
+        Intrinsic::Pop,
+        Intrinsic::Get,
+        Intrinsic::Set,
+        Intrinsic::Has,
+        Intrinsic::Remove,
+        Intrinsic::Len,
+        Intrinsic::Throw,
+        Intrinsic::ReadFile,
+        Intrinsic::WriteFile,
+        Intrinsic::Assert,
+    ];
+
+    /// Returns how many values the intrinsic pops from and pushes onto the stack.
+    pub fn stack_effect(self) -> (u32, u32) {
+        match self {
+            Intrinsic::Dup => (1, 2),
+            Intrinsic::Drop => (1, 0),
+            Intrinsic::Swap => (2, 2),
+            Intrinsic::Over => (2, 3),
+            Intrinsic::Rot => (3, 3),
+            Intrinsic::Add
+            | Intrinsic::Sub
+            | Intrinsic::Mul
+            | Intrinsic::Div
+            | Intrinsic::Rem
+            | Intrinsic::Eq
+            | Intrinsic::Lt
+            | Intrinsic::Le
+            | Intrinsic::Gt
+            | Intrinsic::Ge
+            | Intrinsic::Concat
+            | Intrinsic::Push
+            | Intrinsic::Get
+            | Intrinsic::Has
+            | Intrinsic::Remove => (2, 1),
+            Intrinsic::Not | Intrinsic::ToString | Intrinsic::Len | Intrinsic::ReadFile => (1, 1),
+            Intrinsic::Print | Intrinsic::Throw | Intrinsic::Assert => (1, 0),
+            Intrinsic::Array | Intrinsic::Map => (0, 1),
+            Intrinsic::Pop => (1, 2),
+            Intrinsic::Set => (3, 1),
+            Intrinsic::WriteFile => (2, 0),
+        }
+    }
+}
+
+impl Vm {
+    pub(super) fn intrinsic(&mut self, intrinsic: Intrinsic) -> Result<()> {
+        match intrinsic {
+            Intrinsic::Dup => {
+                let a = self.pop()?;
+                self.push(a);
+                self.push(a);
+            }
+            Intrinsic::Drop => _ = self.pop()?,
+            Intrinsic::Swap => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.push(b);
+                self.push(a);
+            }
+            Intrinsic::Over => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.push(a);
+                self.push(b);
+                self.push(a);
+            }
+            Intrinsic::Rot => {
+                let c = self.pop()?;
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.push(b);
+                self.push(c);
+                self.push(a);
+            }
+            Intrinsic::Add => self.arithmetic(|a, b| Some(a.wrapping_add(b)), |a, b| a + b)?,
+            Intrinsic::Sub => self.arithmetic(|a, b| Some(a.wrapping_sub(b)), |a, b| a - b)?,
+            Intrinsic::Mul => self.arithmetic(|a, b| Some(a.wrapping_mul(b)), |a, b| a * b)?,
+            Intrinsic::Div => {
+                self.arithmetic(|a, b| (b != 0).then(|| a.wrapping_div(b)), |a, b| a / b)?
+            }
+            Intrinsic::Rem => {
+                self.arithmetic(|a, b| (b != 0).then(|| a.wrapping_rem(b)), |a, b| a % b)?
+            }
+            Intrinsic::Eq => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let equals = self.heap.equals(a, b);
+                self.push(equals.into());
+            }
+            Intrinsic::Lt => self.compare(|a, b| a < b, |a, b| a < b)?,
+            Intrinsic::Le => self.compare(|a, b| a <= b, |a, b| a <= b)?,
+            Intrinsic::Gt => self.compare(|a, b| a > b, |a, b| a > b)?,
+            Intrinsic::Ge => self.compare(|a, b| a >= b, |a, b| a >= b)?,
+            Intrinsic::Not => {
+                let a = self.pop_integer()?;
+                self.push((a == 0).into());
+            }
+            Intrinsic::ToString => {
+                let a = self.pop()?;
+                let string = self.heap.display(a);
+                let string = self.heap.alloc_string(string);
+                self.push(string);
+            }
+            Intrinsic::Concat => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let string = format!("{}{}", self.string_of(a)?, self.string_of(b)?);
+                let string = self.heap.alloc_string(string);
+                self.push(string);
+            }
+            Intrinsic::Print => {
+                let a = self.pop()?;
+                writeln!(self.output, "{}", self.heap.display(a))
+                    .map_err(|err| RuntimeErrorKind::Io(err.to_string()))?;
+            }
+            Intrinsic::Array => {
+                let array = self.heap.alloc(Object::Array(Vec::new()));
+                self.push(Value::Object(array));
+            }
+            Intrinsic::Map => {
+                let map = self.heap.alloc(Object::Map(HashMap::new()));
+                self.push(Value::Object(map));
+            }
+            Intrinsic::Push => {
+                let value = self.pop()?;
+                let array = self.pop()?;
+                match self.object_mut(array)? {
+                    Object::Array(elements) => elements.push(value),
+                    object => return Err(type_mismatch("array", object.type_name())),
+                }
+                self.heap.grow(size_of::<Value>());
+                self.push(array);
+            }
+            Intrinsic::Pop => {
+                let array = self.pop()?;
+                let value = match self.object_mut(array)? {
+                    Object::Array(elements) => {
+                        elements.pop().ok_or(RuntimeErrorKind::EmptyArray)?
+                    }
+                    object => return Err(type_mismatch("array", object.type_name())),
+                };
+                self.push(array);
+                self.push(value);
+            }
+            Intrinsic::Get => {
+                let key = self.pop()?;
+                let collection = self.pop()?;
+                let value = match self.object(collection)? {
+                    Object::Array(elements) => elements[self.index(key, elements.len())?],
+                    Object::Map(entries) => {
+                        let key = self.key(key)?;
+                        *entries.get(&key).ok_or(RuntimeErrorKind::KeyNotFound)?
+                    }
+                    object => return Err(type_mismatch("collection", object.type_name())),
+                };
+                self.push(value);
+            }
+            Intrinsic::Set => {
+                let value = self.pop()?;
+                let key = self.pop()?;
+                let collection = self.pop()?;
+                match self.object(collection)? {
+                    Object::Array(elements) => {
+                        let index = self.index(key, elements.len())?;
+                        let Object::Array(elements) = self.object_mut(collection)? else {
+                            unreachable!()
+                        };
+                        elements[index] = value;
+                    }
+                    Object::Map(_) => {
+                        let key = self.key(key)?;
+                        let Object::Map(entries) = self.object_mut(collection)? else {
+                            unreachable!()
+                        };
+                        if entries.insert(key, value).is_none() {
+                            self.heap.grow(size_of::<(Key, Value)>());
+                        }
+                    }
+                    object => return Err(type_mismatch("collection", object.type_name())),
+                }
+                self.push(collection);
+            }
+            Intrinsic::Has => {
+                let key = self.pop()?;
+                let map = self.pop()?;
+                let key = self.key(key)?;
+                let has = match self.object(map)? {
+                    Object::Map(entries) => entries.contains_key(&key),
+                    object => return Err(type_mismatch("map", object.type_name())),
+                };
+                self.push(has.into());
+            }
+            Intrinsic::Remove => {
+                let key = self.pop()?;
+                let map = self.pop()?;
+                let key = self.key(key)?;
+                match self.object_mut(map)? {
+                    Object::Map(entries) => _ = entries.remove(&key),
+                    object => return Err(type_mismatch("map", object.type_name())),
+                }
+                self.push(map);
+            }
+            Intrinsic::Len => {
+                let collection = self.pop()?;
+                let len = match self.object(collection)? {
+                    Object::String(string) => string.chars().count(),
+                    Object::Array(elements) => elements.len(),
+                    Object::Map(entries) => entries.len(),
+                };
+                self.push(Value::Integer(len as i64));
+            }
+            Intrinsic::Throw => {
+                let value = self.pop()?;
+                let message = self.heap.display(value);
+                return Err(RuntimeErrorKind::Thrown { value, message }.into());
+            }
+            Intrinsic::ReadFile => {
+                let path = self.pop()?;
+                let content = fs::read_to_string(self.string_of(path)?)
+                    .map_err(|err| RuntimeErrorKind::Io(err.to_string()))?;
+                let content = self.heap.alloc_string(content);
+                self.push(content);
+            }
+            Intrinsic::WriteFile => {
+                let path = self.pop()?;
+                let content = self.pop()?;
+                fs::write(self.string_of(path)?, self.string_of(content)?)
+                    .map_err(|err| RuntimeErrorKind::Io(err.to_string()))?;
+            }
+            Intrinsic::Assert => {
+                if self.pop_integer()? == 0 {
+                    return Err(RuntimeErrorKind::AssertionFailed.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies an arithmetic operation. The integer operation returns `None` on division by zero.
+    fn arithmetic(
+        &mut self,
+        integer: fn(i64, i64) -> Option<i64>,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        let result = match (a, b) {
+            (Value::Integer(a), Value::Integer(b)) => {
+                Value::Integer(integer(a, b).ok_or(RuntimeErrorKind::DivisionByZero)?)
+            }
+            (Value::Integer(a), Value::Float(b)) => Value::Float(float(a as f64, b)),
+            (Value::Float(a), Value::Integer(b)) => Value::Float(float(a, b as f64)),
+            (Value::Float(a), Value::Float(b)) => Value::Float(float(a, b)),
+            (Value::Object(_), _) => return Err(type_mismatch("number", self.heap.type_name(a))),
+            (_, Value::Object(_)) => return Err(type_mismatch("number", self.heap.type_name(b))),
+        };
+        self.push(result);
+        Ok(())
+    }
+
+    fn compare(
+        &mut self,
+        integer: fn(&i64, &i64) -> bool,
+        float: fn(&f64, &f64) -> bool,
+    ) -> Result<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        let result = match (a, b) {
+            (Value::Integer(a), Value::Integer(b)) => integer(&a, &b),
+            (Value::Integer(a), Value::Float(b)) => float(&(a as f64), &b),
+            (Value::Float(a), Value::Integer(b)) => float(&a, &(b as f64)),
+            (Value::Float(a), Value::Float(b)) => float(&a, &b),
+            (Value::Object(_), _) => return Err(type_mismatch("number", self.heap.type_name(a))),
+            (_, Value::Object(_)) => return Err(type_mismatch("number", self.heap.type_name(b))),
+        };
+        self.push(result.into());
+        Ok(())
+    }
+
+    fn object(&self, value: Value) -> Result<&Object> {
+        match value {
+            Value::Object(object) => Ok(self.heap.get(object)),
+            _ => Err(type_mismatch("object", self.heap.type_name(value))),
+        }
+    }
+
+    fn object_mut(&mut self, value: Value) -> Result<&mut Object> {
+        match value {
+            Value::Object(object) => Ok(self.heap.get_mut(object)),
+            _ => Err(type_mismatch("object", self.heap.type_name(value))),
+        }
+    }
+
+    fn string_of(&self, value: Value) -> Result<&str> {
+        match self.object(value)? {
+            Object::String(string) => Ok(string),
+            object => Err(type_mismatch("string", object.type_name())),
+        }
+    }
+
+    fn index(&self, value: Value, len: usize) -> Result<usize> {
+        let Value::Integer(index) = value else {
+            return Err(type_mismatch("integer", self.heap.type_name(value)));
+        };
+        if index < 0 || index as usize >= len {
+            return Err(RuntimeErrorKind::IndexOutOfBounds { index, len }.into());
+        }
+        Ok(index as usize)
+    }
+
+    fn key(&self, value: Value) -> Result<Key> {
+        self.heap
+            .key(value)
+            .ok_or_else(|| type_mismatch("key", self.heap.type_name(value)))
+    }
+}
+
+fn type_mismatch(expected: &'static str, got: &'static str) -> RuntimeError {
+    RuntimeErrorKind::TypeMismatch { expected, got }.into()
+}
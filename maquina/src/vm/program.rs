@@ -0,0 +1,50 @@
+use std::rc::Rc;
+
+use super::intrinsic::Intrinsic;
+
+#[derive(Clone, Debug, Default)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    pub globals: u32,
+    /// Function called by [Vm::run](super::Vm::run).
+    pub entry: Option<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub name: Rc<str>,
+    pub locals: u32,
+    pub code: Vec<Instruction>,
+    pub debug: Option<DebugInfo>,
+}
+
+/// Maps the instructions of a function back to their source.
+#[derive(Clone, Debug)]
+pub struct DebugInfo {
+    pub path: Rc<str>,
+    /// Line and column of each instruction
+    pub positions: Vec<(u32, u32)>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Instruction {
+    Integer(i64),
+    Float(f64),
+    String(Rc<str>),
+    Load(u32),
+    Store(u32),
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+    Call(u32),
+    /// Call that replaces the current frame.
+    TailCall(u32),
+    Return,
+    Jump(u32),
+    /// Pops an integer and jumps if it is zero.
+    JumpIfZero(u32),
+    /// Installs an error handler at the given instruction of the current function.
+    Try(u32),
+    /// Removes the innermost error handler.
+    EndTry,
+    Intrinsic(Intrinsic),
+}
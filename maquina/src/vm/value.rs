@@ -0,0 +1,20 @@
+use super::heap::ObjectRef;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    Object(ObjectRef),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Self::Integer(0)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Integer(value as i64)
+    }
+}
@@ -0,0 +1,76 @@
+use std::io;
+
+use maquina::vm::{
+    bytecode::{read_program, write_program},
+    intrinsic::Intrinsic,
+    program::{Function, Instruction, Program},
+};
+
+fn program(code: Vec<Instruction>, locals: u32, globals: u32) -> Program {
+    Program {
+        functions: vec![Function {
+            name: "main".into(),
+            locals,
+            code,
+            debug: None,
+        }],
+        globals,
+        entry: Some(0),
+    }
+}
+
+fn round_trip(program: &Program) -> io::Result<Program> {
+    let mut bytes = Vec::new();
+    write_program(&mut bytes, program).expect("write program");
+    read_program(&mut bytes.as_slice())
+}
+
+#[test]
+fn reads_written_programs() {
+    let code = vec![
+        Instruction::Try(6),
+        Instruction::Integer(1),
+        Instruction::Store(0),
+        Instruction::Load(0),
+        Instruction::StoreGlobal(0),
+        Instruction::EndTry,
+        Instruction::Intrinsic(Intrinsic::Drop),
+        Instruction::Jump(8),
+        Instruction::Return,
+    ];
+    let program = round_trip(&program(code, 1, 1)).expect("read program");
+    assert_eq!(program.entry, Some(0));
+    assert_eq!(program.functions[0].code.len(), 9);
+}
+
+#[test]
+fn rejects_invalid_operands() {
+    let invalid = [
+        (Instruction::Call(1), 0, 0),
+        (Instruction::TailCall(1), 0, 0),
+        (Instruction::Load(1), 1, 0),
+        (Instruction::Store(0), 0, 0),
+        (Instruction::LoadGlobal(0), 0, 0),
+        (Instruction::StoreGlobal(2), 0, 2),
+        (Instruction::Jump(2), 0, 0),
+        (Instruction::JumpIfZero(2), 0, 0),
+        (Instruction::Try(2), 0, 0),
+    ];
+    for (instruction, locals, globals) in invalid {
+        let program = program(
+            vec![instruction.clone(), Instruction::Return],
+            locals,
+            globals,
+        );
+        let err = round_trip(&program).expect_err("invalid operand");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{instruction:?}");
+    }
+}
+
+#[test]
+fn rejects_functions_without_return() {
+    for code in [vec![], vec![Instruction::Integer(1)]] {
+        let err = round_trip(&program(code, 0, 0)).expect_err("missing return");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
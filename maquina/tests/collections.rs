@@ -0,0 +1,189 @@
+use maquina::vm::{
+    error::RuntimeErrorKind,
+    intrinsic::Intrinsic,
+    program::{Function, Instruction, Program},
+    value::Value,
+    Vm,
+};
+
+fn program(code: Vec<Instruction>, locals: u32) -> Program {
+    Program {
+        functions: vec![Function {
+            name: "main".into(),
+            locals,
+            code,
+            debug: None,
+        }],
+        globals: 0,
+        entry: Some(0),
+    }
+}
+
+fn run(code: Vec<Instruction>, locals: u32) -> Vm {
+    let mut vm = Vm::new(program(code, locals));
+    vm.run().expect("run program");
+    vm
+}
+
+fn run_err(code: Vec<Instruction>) -> RuntimeErrorKind {
+    Vm::new(program(code, 0)).run().unwrap_err().kind
+}
+
+#[test]
+fn concatenates_strings() {
+    let vm = run(
+        vec![
+            Instruction::String("hé".into()),
+            Instruction::String("llo".into()),
+            Instruction::Intrinsic(Intrinsic::Concat),
+            Instruction::Intrinsic(Intrinsic::Dup),
+            Instruction::Intrinsic(Intrinsic::Len),
+            Instruction::Integer(42),
+            Instruction::Intrinsic(Intrinsic::ToString),
+            Instruction::Return,
+        ],
+        0,
+    );
+    let &[string, len, number] = vm.stack() else {
+        panic!("expected three values on the stack");
+    };
+    assert_eq!(vm.heap().display(string), "héllo");
+    assert_eq!(len, Value::Integer(5));
+    assert_eq!(vm.heap().inspect(number), r#""42""#);
+}
+
+#[test]
+fn pushes_pops_and_indexes_arrays() {
+    let vm = run(
+        vec![
+            Instruction::Intrinsic(Intrinsic::Array),
+            Instruction::Integer(1),
+            Instruction::Intrinsic(Intrinsic::Push),
+            Instruction::Integer(2),
+            Instruction::Intrinsic(Intrinsic::Push),
+            Instruction::String("three".into()),
+            Instruction::Intrinsic(Intrinsic::Push),
+            Instruction::Integer(1),
+            Instruction::Integer(20),
+            Instruction::Intrinsic(Intrinsic::Set),
+            Instruction::Intrinsic(Intrinsic::Pop),
+            Instruction::Store(0),
+            Instruction::Intrinsic(Intrinsic::Dup),
+            Instruction::Intrinsic(Intrinsic::Len),
+            Instruction::Intrinsic(Intrinsic::Swap),
+            Instruction::Integer(1),
+            Instruction::Intrinsic(Intrinsic::Get),
+            Instruction::Load(0),
+            Instruction::Return,
+        ],
+        1,
+    );
+    let &[len, second, popped] = vm.stack() else {
+        panic!("expected three values on the stack");
+    };
+    assert_eq!(len, Value::Integer(2));
+    assert_eq!(second, Value::Integer(20));
+    assert_eq!(vm.heap().display(popped), "three");
+}
+
+#[test]
+fn sets_and_removes_map_entries() {
+    let vm = run(
+        vec![
+            Instruction::Intrinsic(Intrinsic::Map),
+            Instruction::String("a".into()),
+            Instruction::Integer(1),
+            Instruction::Intrinsic(Intrinsic::Set),
+            Instruction::Integer(2),
+            Instruction::String("two".into()),
+            Instruction::Intrinsic(Intrinsic::Set),
+            Instruction::String("a".into()),
+            Instruction::Integer(3),
+            Instruction::Intrinsic(Intrinsic::Set),
+            Instruction::Store(0),
+            Instruction::Load(0),
+            Instruction::Intrinsic(Intrinsic::Len),
+            Instruction::Load(0),
+            Instruction::String("a".into()),
+            Instruction::Intrinsic(Intrinsic::Get),
+            Instruction::Load(0),
+            Instruction::Integer(2),
+            Instruction::Intrinsic(Intrinsic::Remove),
+            Instruction::Integer(2),
+            Instruction::Intrinsic(Intrinsic::Has),
+            Instruction::Load(0),
+            Instruction::Return,
+        ],
+        1,
+    );
+    let &[len, a, has, map] = vm.stack() else {
+        panic!("expected four values on the stack");
+    };
+    assert_eq!(len, Value::Integer(2));
+    assert_eq!(a, Value::Integer(3));
+    assert_eq!(has, Value::Integer(0));
+    assert_eq!(vm.heap().display(map), r#"{"a": 3}"#);
+}
+
+#[test]
+fn reports_missing_elements() {
+    let kind = run_err(vec![
+        Instruction::Intrinsic(Intrinsic::Array),
+        Instruction::Integer(0),
+        Instruction::Intrinsic(Intrinsic::Get),
+        Instruction::Return,
+    ]);
+    assert_eq!(
+        kind,
+        RuntimeErrorKind::IndexOutOfBounds { index: 0, len: 0 }
+    );
+
+    let kind = run_err(vec![
+        Instruction::Intrinsic(Intrinsic::Map),
+        Instruction::String("missing".into()),
+        Instruction::Intrinsic(Intrinsic::Get),
+        Instruction::Return,
+    ]);
+    assert_eq!(kind, RuntimeErrorKind::KeyNotFound);
+
+    let kind = run_err(vec![
+        Instruction::Intrinsic(Intrinsic::Array),
+        Instruction::Intrinsic(Intrinsic::Pop),
+        Instruction::Return,
+    ]);
+    assert_eq!(kind, RuntimeErrorKind::EmptyArray);
+
+    let kind = run_err(vec![
+        Instruction::Intrinsic(Intrinsic::Map),
+        Instruction::Intrinsic(Intrinsic::Array),
+        Instruction::Integer(1),
+        Instruction::Intrinsic(Intrinsic::Set),
+        Instruction::Return,
+    ]);
+    assert_eq!(
+        kind,
+        RuntimeErrorKind::TypeMismatch {
+            expected: "key",
+            got: "array"
+        }
+    );
+}
+
+#[test]
+fn overwriting_map_entries_does_not_grow_the_heap() {
+    let program = |sets: usize| {
+        let mut code = vec![Instruction::Intrinsic(Intrinsic::Map)];
+        code.extend((0..sets).flat_map(|_| {
+            [
+                Instruction::Integer(1),
+                Instruction::Integer(2),
+                Instruction::Intrinsic(Intrinsic::Set),
+            ]
+        }));
+        code.push(Instruction::Return);
+        code
+    };
+    let once = run(program(1), 0);
+    let many = run(program(100), 0);
+    assert_eq!(many.heap().bytes(), once.heap().bytes());
+}
@@ -0,0 +1,172 @@
+use maquina::vm::{
+    error::RuntimeErrorKind,
+    heap::{HeapConfig, Object},
+    intrinsic::Intrinsic,
+    program::{Function, Instruction, Program},
+    value::Value,
+    Config, Vm,
+};
+
+fn program(code: Vec<Instruction>, locals: u32, globals: u32) -> Program {
+    Program {
+        functions: vec![Function {
+            name: "main".into(),
+            locals,
+            code,
+            debug: None,
+        }],
+        globals,
+        entry: Some(0),
+    }
+}
+
+fn config(gc_threshold: usize, limit: Option<usize>) -> Config {
+    Config {
+        heap: HeapConfig {
+            gc_threshold,
+            limit,
+        },
+        ..Config::default()
+    }
+}
+
+/// Builds an array that contains itself and drops it.
+fn self_cycle() -> [Instruction; 4] {
+    [
+        Instruction::Intrinsic(Intrinsic::Array),
+        Instruction::Intrinsic(Intrinsic::Dup),
+        Instruction::Intrinsic(Intrinsic::Push),
+        Instruction::Intrinsic(Intrinsic::Drop),
+    ]
+}
+
+/// Builds two maps that reference each other and drops them.
+fn map_cycle() -> [Instruction; 12] {
+    [
+        Instruction::Intrinsic(Intrinsic::Map),
+        Instruction::Store(0),
+        Instruction::Intrinsic(Intrinsic::Map),
+        Instruction::String("other".into()),
+        Instruction::Load(0),
+        Instruction::Intrinsic(Intrinsic::Set),
+        Instruction::Store(1),
+        Instruction::Load(0),
+        Instruction::String("other".into()),
+        Instruction::Load(1),
+        Instruction::Intrinsic(Intrinsic::Set),
+        Instruction::Intrinsic(Intrinsic::Drop),
+    ]
+}
+
+#[test]
+fn collects_cycles() {
+    let mut code: Vec<_> = (0..10_000).flat_map(|_| self_cycle()).collect();
+    code.extend((0..10_000).flat_map(|_| map_cycle()));
+    code.extend([
+        Instruction::Integer(0),
+        Instruction::Store(0),
+        Instruction::Integer(0),
+        Instruction::Store(1),
+        Instruction::Return,
+    ]);
+    let mut vm = Vm::with_config(program(code, 2, 0), config(4096, Some(16384)));
+    vm.run().expect("run program");
+    vm.collect_garbage().expect("collect garbage");
+    let stats = vm.heap().stats();
+    assert!(stats.collections > 1);
+    assert_eq!(stats.live_objects, 0);
+    assert_eq!(vm.heap().len(), 0);
+    assert_eq!(stats.freed_objects, stats.allocated_objects);
+}
+
+#[test]
+fn keeps_reachable_cycles() {
+    let mut code = vec![
+        Instruction::Intrinsic(Intrinsic::Array),
+        Instruction::Intrinsic(Intrinsic::Dup),
+        Instruction::Intrinsic(Intrinsic::Push),
+        Instruction::String("alive".into()),
+        Instruction::Intrinsic(Intrinsic::Push),
+        Instruction::StoreGlobal(0),
+    ];
+    code.extend((0..10_000).flat_map(|_| self_cycle()));
+    code.extend([
+        Instruction::LoadGlobal(0),
+        Instruction::Integer(1),
+        Instruction::Intrinsic(Intrinsic::Get),
+        Instruction::Return,
+    ]);
+    let mut vm = Vm::with_config(program(code, 0, 1), config(4096, None));
+    vm.run().expect("run program");
+    vm.collect_garbage().expect("collect garbage");
+    assert!(vm.heap().stats().collections > 1);
+    assert_eq!(vm.heap().len(), 2);
+    let &[Value::Object(string)] = vm.stack() else {
+        panic!("expected a single object on the stack");
+    };
+    assert!(matches!(vm.heap().get(string), Object::String(string) if &**string == "alive"));
+    assert_eq!(vm.heap().display(vm.globals()[0]), r#"[... "alive"]"#);
+}
+
+#[test]
+fn enforces_heap_limit() {
+    let mut code = vec![Instruction::Intrinsic(Intrinsic::Array)];
+    code.extend((0..10_000).flat_map(|_| {
+        [
+            Instruction::Integer(1),
+            Instruction::Intrinsic(Intrinsic::Push),
+        ]
+    }));
+    code.push(Instruction::Return);
+    let mut vm = Vm::with_config(program(code, 0, 0), config(1024, Some(4096)));
+    let err = vm.run().expect_err("heap limit exceeded");
+    assert_eq!(err.kind, RuntimeErrorKind::HeapLimitExceeded);
+    assert!(matches!(
+        err.instruction,
+        Some(Instruction::Intrinsic(Intrinsic::Push))
+    ));
+}
+
+#[test]
+fn heap_limit_cannot_be_caught() {
+    let mut code = vec![
+        Instruction::Try(0),
+        Instruction::Intrinsic(Intrinsic::Array),
+    ];
+    code.extend((0..10_000).flat_map(|_| {
+        [
+            Instruction::Integer(1),
+            Instruction::Intrinsic(Intrinsic::Push),
+        ]
+    }));
+    code.extend([Instruction::EndTry, Instruction::Return]);
+    let handler = code.len() as u32 - 1;
+    code[0] = Instruction::Try(handler);
+    let mut vm = Vm::with_config(program(code, 0, 0), config(1024, Some(4096)));
+    let err = vm.run().expect_err("heap limit exceeded");
+    assert_eq!(err.kind, RuntimeErrorKind::HeapLimitExceeded);
+}
+
+#[test]
+fn does_not_collect_every_step_near_the_limit() {
+    let mut code = vec![Instruction::Intrinsic(Intrinsic::Array)];
+    code.extend((0..100).flat_map(|_| {
+        [
+            Instruction::Integer(1),
+            Instruction::Intrinsic(Intrinsic::Push),
+        ]
+    }));
+    code.push(Instruction::StoreGlobal(0));
+    code.extend((0..10_000).flat_map(|_| {
+        [
+            Instruction::String("garbage".into()),
+            Instruction::Intrinsic(Intrinsic::Drop),
+        ]
+    }));
+    code.push(Instruction::Return);
+    let mut vm = Vm::with_config(program(code, 0, 1), config(1024, Some(2304)));
+    vm.run().expect("run program");
+    let stats = vm.heap().stats();
+    assert!(stats.live_bytes > 2048);
+    assert!(stats.collections < 1000);
+}
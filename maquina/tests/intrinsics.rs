@@ -0,0 +1,8 @@
+use maquina::vm::intrinsic::Intrinsic;
+
+#[test]
+fn all_is_ordered_by_discriminant() {
+    for (i, intrinsic) in Intrinsic::ALL.iter().enumerate() {
+        assert_eq!(*intrinsic as usize, i, "{intrinsic:?}");
+    }
+}